@@ -0,0 +1,21 @@
+//! Generates the parser of the interpreter from `grammar.txt`.
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[path = "src/grammar.rs"]
+#[allow(dead_code)]
+mod grammar;
+
+fn main() {
+    println!("cargo:rerun-if-changed=grammar.txt");
+    println!("cargo:rerun-if-changed=src/grammar.rs");
+    let text = fs::read_to_string("grammar.txt").expect("can not read grammar.txt");
+    let grammar = grammar::Grammar::parse(&text).unwrap_or_else(|err| panic!("grammar.txt: {}", err));
+    let table = grammar.analyze();
+    if !table.conflicts.is_empty() {
+        panic!("grammar.txt is not LL(1):\n{}", table.conflicts.join("\n"));
+    }
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not set");
+    fs::write(Path::new(&out_dir).join("grammar.rs"), grammar.generate(&table)).expect("can not write the parser");
+}
@@ -1,48 +1,513 @@
-use std::vec;
-use super::tokenizer;
-
-pub enum NodeType {
-    Root,
-    Print,
-    Assign,
-    Add,
-    Mul,
-    Pow,
-    Name,
-    Integer,
-    FuncDecl,
-    FuncBody,
-    FuncCall,
-    Return,
-    Param,
-    Arg,
-    ParamList,
-    ArgList,
-}
-
-// pub trait NodeTrait {
-//     pub token(&self) -> tokenizer::TokenType;
-//     pub type(&self) -> NodeType;
-//     pub childs(&self) -> std::vec::Vec<NodeTrait>;
-// }
-
-pub struct AstNode {
-    pub token: tokenizer::Token,
-    pub node_type: NodeType,
-    pub childs: vec::Vec<AstNode>,
-}
-
-impl AstNode {
-    pub fn new(node_type: NodeType, token: tokenizer::Token) -> AstNode {
-        AstNode {
-            token: token,
-            node_type: node_type,
-            childs: vec::Vec::<AstNode>::new(),
-        }
-    }
-
-    pub fn add_node(&mut self, node: AstNode) {
-        self.childs.push(node);
-    }
-}
-
+use std::rc::Rc;
+use super::tokenizer::Token;
+
+/// Every node keeps the token it starts at, or its operator token, for
+/// error positions.
+#[derive(Clone, PartialEq)]
+pub enum Stmt {
+    Print {
+        token: Token,
+        value: Expr,
+    },
+    Assign {
+        token: Token,
+        target: Target,
+        value: Expr,
+    },
+    While {
+        token: Token,
+        label: Option<Token>,
+        condition: Expr,
+        body: Vec<Stmt>,
+    },
+    For {
+        token: Token,
+        label: Option<Token>,
+        target: Target,
+        iterable: Expr,
+        body: Vec<Stmt>,
+    },
+    Break {
+        token: Token,
+        label: Option<Token>,
+    },
+    Continue {
+        token: Token,
+        label: Option<Token>,
+    },
+    Return {
+        token: Token,
+        value: Option<Expr>,
+    },
+    FuncDecl {
+        name: Token,
+        function: Rc<FuncDef>,
+    },
+    OperatorDecl {
+        op: Token,
+        precedence: i32,
+        function: Rc<FuncDef>,
+    },
+    /// An expression evaluated for its side effects, e.g. a call.
+    Expression {
+        value: Expr,
+    },
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Expr {
+    Integer {
+        token: Token,
+        value: i64,
+    },
+    /// `token.literal` is the value with escapes resolved.
+    Str {
+        token: Token,
+    },
+    Name {
+        token: Token,
+    },
+    List {
+        token: Token,
+        items: Vec<Expr>,
+    },
+    Tuple {
+        token: Token,
+        items: Vec<Expr>,
+    },
+    Map {
+        token: Token,
+        pairs: Vec<(Expr, Expr)>,
+    },
+    Index {
+        token: Token,
+        target: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// `target.name`
+    Member {
+        token: Token,
+        target: Box<Expr>,
+        name: Token,
+    },
+    /// `token` is the callee's token for the common `name(args)` case.
+    Call {
+        token: Token,
+        callee: Box<Expr>,
+        args: Vec<Arg>,
+    },
+    Lambda {
+        token: Token,
+        function: Rc<FuncDef>,
+    },
+    Unary {
+        op: UnaryOp,
+        token: Token,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        token: Token,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+    /// `a..b`
+    Range,
+    /// `a..=b`
+    RangeInclusive,
+    /// A user defined operator.
+    Custom(String),
+}
+
+/// Assignment and `for` loop targets.
+#[derive(Clone, PartialEq)]
+pub enum Target {
+    Name {
+        token: Token,
+    },
+    Index {
+        token: Token,
+        target: Expr,
+        index: Expr,
+    },
+    Member {
+        token: Token,
+        target: Expr,
+        name: Token,
+    },
+    /// `a, b = ...` and `(a, b) = ...`
+    Tuple {
+        token: Token,
+        items: Vec<Target>,
+    },
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Arg {
+    Positional(Expr),
+    Named {
+        name: Token,
+        value: Expr,
+    },
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Param {
+    Required {
+        name: Token,
+    },
+    /// The default is evaluated at call time.
+    Optional {
+        name: Token,
+        default: Expr,
+    },
+    /// `...rest`, always the last parameter.
+    Variadic {
+        name: Token,
+    },
+}
+
+/// Parameters and body shared by function declarations, lambdas and
+/// operator declarations.
+#[derive(Clone, PartialEq)]
+pub struct FuncDef {
+    pub params: Vec<Param>,
+    pub body: Vec<Stmt>,
+}
+
+impl Expr {
+    pub fn token(&self) -> &Token {
+        match self {
+            Expr::Integer { token, .. } | Expr::Str { token } | Expr::Name { token } |
+            Expr::List { token, .. } | Expr::Tuple { token, .. } | Expr::Map { token, .. } |
+            Expr::Index { token, .. } | Expr::Member { token, .. } | Expr::Call { token, .. } |
+            Expr::Lambda { token, .. } | Expr::Unary { token, .. } | Expr::Binary { token, .. } => token,
+        }
+    }
+}
+
+impl Target {
+    pub fn token(&self) -> &Token {
+        match self {
+            Target::Name { token } | Target::Index { token, .. } | Target::Member { token, .. } |
+            Target::Tuple { token, .. } => token,
+        }
+    }
+}
+
+impl Param {
+    pub fn name(&self) -> &Token {
+        match self {
+            Param::Required { name } | Param::Optional { name, .. } | Param::Variadic { name } => name,
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn as_str(&self) -> &str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Pow => "^",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+            BinaryOp::Range => "..",
+            BinaryOp::RangeInclusive => "..=",
+            BinaryOp::Custom(op) => op,
+        }
+    }
+}
+
+/// Read-only traversal of the AST. Every `visit_*` method defaults to the
+/// matching `walk_*` function, which visits the children of the node, so a
+/// pass only overrides the node kinds it cares about and calls `walk_*`
+/// itself to keep descending.
+pub trait Visitor {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_target(&mut self, target: &Target) {
+        walk_target(self, target);
+    }
+
+    fn visit_arg(&mut self, arg: &Arg) {
+        walk_arg(self, arg);
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        walk_param(self, param);
+    }
+
+    fn visit_func_def(&mut self, function: &FuncDef) {
+        walk_func_def(self, function);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &[Stmt]) {
+    for stmt in program {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::Print { value, .. } | Stmt::Expression { value } => visitor.visit_expr(value),
+        Stmt::Assign { target, value, .. } => {
+            visitor.visit_target(target);
+            visitor.visit_expr(value);
+        },
+        Stmt::While { condition, body, .. } => {
+            visitor.visit_expr(condition);
+            walk_program(visitor, body);
+        },
+        Stmt::For { target, iterable, body, .. } => {
+            visitor.visit_target(target);
+            visitor.visit_expr(iterable);
+            walk_program(visitor, body);
+        },
+        Stmt::Break { .. } | Stmt::Continue { .. } => (),
+        Stmt::Return { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        },
+        Stmt::FuncDecl { function, .. } | Stmt::OperatorDecl { function, .. } => visitor.visit_func_def(function),
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Integer { .. } | Expr::Str { .. } | Expr::Name { .. } => (),
+        Expr::List { items, .. } | Expr::Tuple { items, .. } => {
+            for item in items {
+                visitor.visit_expr(item);
+            }
+        },
+        Expr::Map { pairs, .. } => {
+            for (key, value) in pairs {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        },
+        Expr::Index { target, index, .. } => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(index);
+        },
+        Expr::Member { target, .. } => visitor.visit_expr(target),
+        Expr::Call { callee, args, .. } => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_arg(arg);
+            }
+        },
+        Expr::Lambda { function, .. } => visitor.visit_func_def(function),
+        Expr::Unary { operand, .. } => visitor.visit_expr(operand),
+        Expr::Binary { lhs, rhs, .. } => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        },
+    }
+}
+
+pub fn walk_target<V: Visitor + ?Sized>(visitor: &mut V, target: &Target) {
+    match target {
+        Target::Name { .. } => (),
+        Target::Index { target, index, .. } => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(index);
+        },
+        Target::Member { target, .. } => visitor.visit_expr(target),
+        Target::Tuple { items, .. } => {
+            for item in items {
+                visitor.visit_target(item);
+            }
+        },
+    }
+}
+
+pub fn walk_arg<V: Visitor + ?Sized>(visitor: &mut V, arg: &Arg) {
+    match arg {
+        Arg::Positional(value) | Arg::Named { value, .. } => visitor.visit_expr(value),
+    }
+}
+
+pub fn walk_param<V: Visitor + ?Sized>(visitor: &mut V, param: &Param) {
+    if let Param::Optional { default, .. } = param {
+        visitor.visit_expr(default);
+    }
+}
+
+pub fn walk_func_def<V: Visitor + ?Sized>(visitor: &mut V, function: &FuncDef) {
+    for param in &function.params {
+        visitor.visit_param(param);
+    }
+    walk_program(visitor, &function.body);
+}
+
+/// Like `Visitor` but may rewrite the tree in place. Function definitions
+/// are shared through `Rc`, a shared one is copied before it is changed.
+pub trait VisitorMut {
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_target_mut(&mut self, target: &mut Target) {
+        walk_target_mut(self, target);
+    }
+
+    fn visit_arg_mut(&mut self, arg: &mut Arg) {
+        walk_arg_mut(self, arg);
+    }
+
+    fn visit_param_mut(&mut self, param: &mut Param) {
+        walk_param_mut(self, param);
+    }
+
+    fn visit_func_def_mut(&mut self, function: &mut FuncDef) {
+        walk_func_def_mut(self, function);
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut [Stmt]) {
+    for stmt in program {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Print { value, .. } | Stmt::Expression { value } => visitor.visit_expr_mut(value),
+        Stmt::Assign { target, value, .. } => {
+            visitor.visit_target_mut(target);
+            visitor.visit_expr_mut(value);
+        },
+        Stmt::While { condition, body, .. } => {
+            visitor.visit_expr_mut(condition);
+            walk_program_mut(visitor, body);
+        },
+        Stmt::For { target, iterable, body, .. } => {
+            visitor.visit_target_mut(target);
+            visitor.visit_expr_mut(iterable);
+            walk_program_mut(visitor, body);
+        },
+        Stmt::Break { .. } | Stmt::Continue { .. } => (),
+        Stmt::Return { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        },
+        Stmt::FuncDecl { function, .. } | Stmt::OperatorDecl { function, .. } =>
+            visitor.visit_func_def_mut(Rc::make_mut(function)),
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Integer { .. } | Expr::Str { .. } | Expr::Name { .. } => (),
+        Expr::List { items, .. } | Expr::Tuple { items, .. } => {
+            for item in items {
+                visitor.visit_expr_mut(item);
+            }
+        },
+        Expr::Map { pairs, .. } => {
+            for (key, value) in pairs {
+                visitor.visit_expr_mut(key);
+                visitor.visit_expr_mut(value);
+            }
+        },
+        Expr::Index { target, index, .. } => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_expr_mut(index);
+        },
+        Expr::Member { target, .. } => visitor.visit_expr_mut(target),
+        Expr::Call { callee, args, .. } => {
+            visitor.visit_expr_mut(callee);
+            for arg in args {
+                visitor.visit_arg_mut(arg);
+            }
+        },
+        Expr::Lambda { function, .. } => visitor.visit_func_def_mut(Rc::make_mut(function)),
+        Expr::Unary { operand, .. } => visitor.visit_expr_mut(operand),
+        Expr::Binary { lhs, rhs, .. } => {
+            visitor.visit_expr_mut(lhs);
+            visitor.visit_expr_mut(rhs);
+        },
+    }
+}
+
+pub fn walk_target_mut<V: VisitorMut + ?Sized>(visitor: &mut V, target: &mut Target) {
+    match target {
+        Target::Name { .. } => (),
+        Target::Index { target, index, .. } => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_expr_mut(index);
+        },
+        Target::Member { target, .. } => visitor.visit_expr_mut(target),
+        Target::Tuple { items, .. } => {
+            for item in items {
+                visitor.visit_target_mut(item);
+            }
+        },
+    }
+}
+
+pub fn walk_arg_mut<V: VisitorMut + ?Sized>(visitor: &mut V, arg: &mut Arg) {
+    match arg {
+        Arg::Positional(value) | Arg::Named { value, .. } => visitor.visit_expr_mut(value),
+    }
+}
+
+pub fn walk_param_mut<V: VisitorMut + ?Sized>(visitor: &mut V, param: &mut Param) {
+    if let Param::Optional { default, .. } = param {
+        visitor.visit_expr_mut(default);
+    }
+}
+
+pub fn walk_func_def_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut FuncDef) {
+    for param in &mut function.params {
+        visitor.visit_param_mut(param);
+    }
+    walk_program_mut(visitor, &mut function.body);
+}
@@ -0,0 +1,95 @@
+use super::value::Value;
+
+const BUILTINS: &[&str] = &["len", "keys", "has", "remove", "push", "divmod"];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name)
+}
+
+pub fn names() -> &'static [&'static str] {
+    BUILTINS
+}
+
+/// The static name of a builtin, `name` must satisfy `is_builtin`.
+pub fn name(name: &str) -> &'static str {
+    BUILTINS.iter().find(|builtin| **builtin == name).expect("not a builtin")
+}
+
+fn check_arity(name: &str, args: &[Value], expect: usize) -> Result<(), String> {
+    if args.len() != expect {
+        return Err(format!("{}() takes {} arguments but {} were given", name, expect, args.len()));
+    }
+    Ok(())
+}
+
+fn len(args: Vec<Value>) -> Result<Value, String> {
+    check_arity("len", &args, 1)?;
+    match &args[0] {
+        Value::Str(s) => Ok(Value::Integer(s.chars().count() as i64)),
+        Value::List(list) => Ok(Value::Integer(list.borrow().len() as i64)),
+        Value::Map(map) => Ok(Value::Integer(map.borrow().len() as i64)),
+        Value::Range(start, end) => Ok(Value::Integer(end.saturating_sub(*start).max(0))),
+        other => Err(format!("len() not supported for '{}'", other.type_name())),
+    }
+}
+
+fn keys(args: Vec<Value>) -> Result<Value, String> {
+    check_arity("keys", &args, 1)?;
+    match &args[0] {
+        Value::Map(map) => Ok(Value::new_list(map.borrow().keys().map(|k| k.to_value()).collect())),
+        other => Err(format!("keys() expects a map, found '{}'", other.type_name())),
+    }
+}
+
+fn has(args: Vec<Value>) -> Result<Value, String> {
+    check_arity("has", &args, 2)?;
+    match &args[0] {
+        Value::Map(map) => Ok(Value::Bool(map.borrow().contains_key(&args[1].to_key()?))),
+        other => Err(format!("has() expects a map, found '{}'", other.type_name())),
+    }
+}
+
+/// Removes a key from a map and returns the value it held, or nil.
+fn remove(args: Vec<Value>) -> Result<Value, String> {
+    check_arity("remove", &args, 2)?;
+    match &args[0] {
+        Value::Map(map) => Ok(map.borrow_mut().remove(&args[1].to_key()?).unwrap_or(Value::Nil)),
+        other => Err(format!("remove() expects a map, found '{}'", other.type_name())),
+    }
+}
+
+fn push(args: Vec<Value>) -> Result<Value, String> {
+    check_arity("push", &args, 2)?;
+    match &args[0] {
+        Value::List(list) => {
+            list.borrow_mut().push(args[1].clone());
+            Ok(Value::Nil)
+        },
+        other => Err(format!("push() expects a list, found '{}'", other.type_name())),
+    }
+}
+
+/// Returns the quotient and the remainder as a tuple.
+fn divmod(args: Vec<Value>) -> Result<Value, String> {
+    check_arity("divmod", &args, 2)?;
+    match (&args[0], &args[1]) {
+        (Value::Integer(_), Value::Integer(0)) => Err("division by zero".to_string()),
+        (Value::Integer(l), Value::Integer(r)) => match (l.checked_div(*r), l.checked_rem(*r)) {
+            (Some(quotient), Some(remainder)) => Ok(Value::new_tuple(vec![Value::Integer(quotient), Value::Integer(remainder)])),
+            _ => Err("integer overflow".to_string()),
+        },
+        (l, r) => Err(format!("divmod() expects two ints, found '{}' and '{}'", l.type_name(), r.type_name())),
+    }
+}
+
+pub fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
+    match name {
+        "len" => len(args),
+        "keys" => keys(args),
+        "has" => has(args),
+        "remove" => remove(args),
+        "push" => push(args),
+        "divmod" => divmod(args),
+        _ => Err(format!("builtin function '{}' not found", name)),
+    }
+}
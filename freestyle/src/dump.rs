@@ -0,0 +1,234 @@
+use super::ast::{self, Arg, Expr, FuncDef, Param, Stmt, Target, Visitor};
+use super::tokenizer::Token;
+
+/// Generic form of an AST node shared by the S-expression and JSON dumps.
+/// `row`/`col` is the position of the node's token.
+struct Node {
+    kind: &'static str,
+    literal: Option<String>,
+    row: u32,
+    col: u32,
+    childs: Vec<Node>,
+}
+
+impl Node {
+    fn new(kind: &'static str, token: &Token, literal: Option<&str>) -> Node {
+        Node {
+            kind,
+            literal: literal.map(|literal| literal.to_string()),
+            row: token.row,
+            col: token.col,
+            childs: Vec::new(),
+        }
+    }
+
+    /// Lists such as loop bodies get a node of their own so the dump shows
+    /// where they end, it has no position.
+    fn group(kind: &'static str) -> Node {
+        Node { kind, literal: None, row: 0, col: 0, childs: Vec::new() }
+    }
+}
+
+/// Builds the `Node` tree while visiting the AST.
+struct Builder {
+    /// Nodes built so far for the node being visited.
+    childs: Vec<Node>,
+}
+
+impl Builder {
+    /// Adds `node` with the nodes `visit` builds as its children.
+    fn add(&mut self, mut node: Node, visit: impl FnOnce(&mut Builder)) {
+        let parent = std::mem::take(&mut self.childs);
+        visit(self);
+        node.childs = std::mem::replace(&mut self.childs, parent);
+        self.childs.push(node);
+    }
+
+    fn leaf(&mut self, node: Node) {
+        self.childs.push(node);
+    }
+
+    fn label(&mut self, label: &Option<Token>) {
+        if let Some(token) = label {
+            self.leaf(Node::new("label", token, Some(&token.literal)));
+        }
+    }
+
+    fn block(&mut self, statements: &[Stmt]) {
+        self.add(Node::group("block"), |builder| ast::walk_program(builder, statements));
+    }
+}
+
+impl Visitor for Builder {
+    fn visit_stmt(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Print { token, .. } => self.add(Node::new("print", token, None), |b| ast::walk_stmt(b, statement)),
+            Stmt::Assign { token, .. } => self.add(Node::new("assign", token, None), |b| ast::walk_stmt(b, statement)),
+            Stmt::While { token, label, condition, body } => self.add(Node::new("while", token, None), |b| {
+                b.label(label);
+                b.visit_expr(condition);
+                b.block(body);
+            }),
+            Stmt::For { token, label, target, iterable, body } => self.add(Node::new("for", token, None), |b| {
+                b.label(label);
+                b.visit_target(target);
+                b.visit_expr(iterable);
+                b.block(body);
+            }),
+            Stmt::Break { token, label } => self.add(Node::new("break", token, None), |b| b.label(label)),
+            Stmt::Continue { token, label } => self.add(Node::new("continue", token, None), |b| b.label(label)),
+            Stmt::Return { token, .. } => self.add(Node::new("return", token, None), |b| ast::walk_stmt(b, statement)),
+            Stmt::FuncDecl { name, .. } =>
+                self.add(Node::new("func", name, Some(&name.literal)), |b| ast::walk_stmt(b, statement)),
+            Stmt::OperatorDecl { op, precedence, .. } => self.add(Node::new("operator", op, Some(&op.literal)), |b| {
+                b.leaf(Node::new("precedence", op, Some(&precedence.to_string())));
+                ast::walk_stmt(b, statement);
+            }),
+            Stmt::Expression { value } =>
+                self.add(Node::new("expression", value.token(), None), |b| ast::walk_stmt(b, statement)),
+        }
+    }
+
+    fn visit_expr(&mut self, expression: &Expr) {
+        let node = match expression {
+            Expr::Integer { token, value } => Node::new("integer", token, Some(&value.to_string())),
+            Expr::Str { token } => Node::new("string", token, Some(&token.literal)),
+            Expr::Name { token } => Node::new("name", token, Some(&token.literal)),
+            Expr::List { token, .. } => Node::new("list", token, None),
+            Expr::Tuple { token, .. } => Node::new("tuple", token, None),
+            Expr::Map { token, pairs } => return self.add(Node::new("map", token, None), |b| {
+                for (key, value) in pairs {
+                    b.add(Node::new("pair", key.token(), None), |b| {
+                        b.visit_expr(key);
+                        b.visit_expr(value);
+                    });
+                }
+            }),
+            Expr::Index { token, .. } => Node::new("index", token, None),
+            Expr::Member { token, name, .. } => Node::new("member", token, Some(&name.literal)),
+            Expr::Call { token, .. } => Node::new("call", token, None),
+            Expr::Lambda { token, .. } => Node::new("lambda", token, None),
+            Expr::Unary { token, .. } => Node::new("unary", token, Some(&token.literal)),
+            Expr::Binary { op, token, .. } => Node::new("binary", token, Some(op.as_str())),
+        };
+        self.add(node, |b| ast::walk_expr(b, expression));
+    }
+
+    fn visit_target(&mut self, assign_target: &Target) {
+        let node = match assign_target {
+            Target::Name { token } => Node::new("name", token, Some(&token.literal)),
+            Target::Index { token, .. } => Node::new("index", token, None),
+            Target::Member { token, name, .. } => Node::new("member", token, Some(&name.literal)),
+            Target::Tuple { token, .. } => Node::new("tuple", token, None),
+        };
+        self.add(node, |b| ast::walk_target(b, assign_target));
+    }
+
+    fn visit_arg(&mut self, argument: &Arg) {
+        match argument {
+            Arg::Positional(_) => ast::walk_arg(self, argument),
+            Arg::Named { name, .. } => self.add(Node::new("named", name, Some(&name.literal)), |b| ast::walk_arg(b, argument)),
+        }
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        let node = match param {
+            Param::Required { name } | Param::Optional { name, .. } => Node::new("param", name, Some(&name.literal)),
+            Param::Variadic { name } => Node::new("variadic", name, Some(&name.literal)),
+        };
+        self.add(node, |b| ast::walk_param(b, param));
+    }
+
+    fn visit_func_def(&mut self, function: &FuncDef) {
+        self.add(Node::group("params"), |b| {
+            for param in &function.params {
+                b.visit_param(param);
+            }
+        });
+        self.block(&function.body);
+    }
+}
+
+fn tree(program: &[Stmt]) -> Node {
+    let mut builder = Builder { childs: Vec::new() };
+    builder.add(Node::group("program"), |b| ast::walk_program(b, program));
+    builder.childs.pop().unwrap()
+}
+
+/// Escapes a string for both dump formats, the result is a valid JSON string.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn write_sexpr(node: &Node, indent: usize, out: &mut String) {
+    out.push_str(&" ".repeat(indent));
+    out.push('(');
+    out.push_str(node.kind);
+    if node.row > 0 {
+        out.push_str(&format!(" @{}:{}", node.row, node.col));
+    }
+    if let Some(literal) = &node.literal {
+        out.push(' ');
+        out.push_str(&quote(literal));
+    }
+    for child in &node.childs {
+        out.push('\n');
+        write_sexpr(child, indent + 2, out);
+    }
+    out.push(')');
+}
+
+fn write_json(node: &Node, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent + 2);
+    out.push_str("{\n");
+    out.push_str(&format!("{}\"type\": {}", pad, quote(node.kind)));
+    if let Some(literal) = &node.literal {
+        out.push_str(&format!(",\n{}\"literal\": {}", pad, quote(literal)));
+    }
+    if node.row > 0 {
+        out.push_str(&format!(",\n{}\"span\": {{\"line\": {}, \"column\": {}}}", pad, node.row, node.col));
+    }
+    if !node.childs.is_empty() {
+        out.push_str(&format!(",\n{}\"children\": [\n", pad));
+        for (i, child) in node.childs.iter().enumerate() {
+            out.push_str(&" ".repeat(indent + 4));
+            write_json(child, indent + 4, out);
+            out.push_str(if i + 1 < node.childs.len() { ",\n" } else { "\n" });
+        }
+        out.push_str(&format!("{}]", pad));
+    }
+    out.push('\n');
+    out.push_str(&" ".repeat(indent));
+    out.push('}');
+}
+
+/// Indented S-expression, one node per line:
+/// `(binary @1:7 "+" (name @1:5 "a") ...)`, `@line:column` is the node's span.
+pub fn sexpr(program: &[Stmt]) -> String {
+    let mut out = String::new();
+    write_sexpr(&tree(program), 0, &mut out);
+    out.push('\n');
+    out
+}
+
+/// The same tree as `sexpr` as JSON objects with `type`, `literal`, `span`
+/// and `children` keys, absent keys are omitted.
+pub fn json(program: &[Stmt]) -> String {
+    let mut out = String::new();
+    write_json(&tree(program), 0, &mut out);
+    out.push('\n');
+    out
+}
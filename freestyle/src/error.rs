@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// A user defined function that was active when an error happened.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TraceEntry {
+    pub function: String,
+    pub row: u32,
+    pub col: u32,
+}
+
+/// What went wrong, the command line exits with a different code for each.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ErrorKind {
+    Syntax,
+    Runtime,
+    Io,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Active calls, most recent call first. Empty for errors at top level.
+    pub trace: Vec<TraceEntry>,
+}
+
+impl Error {
+    /// A runtime error.
+    pub fn new(message: String) -> Error {
+        Error {
+            kind: ErrorKind::Runtime,
+            message,
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn syntax(message: String) -> Error {
+        Error { kind: ErrorKind::Syntax, ..Error::new(message) }
+    }
+
+    pub fn io(message: String) -> Error {
+        Error { kind: ErrorKind::Io, ..Error::new(message) }
+    }
+}
+
+/// The tokenizer and the parser report their errors as strings.
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::syntax(message)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if self.trace.is_empty() {
+            return Ok(());
+        }
+        write!(f, "\nstack trace (most recent call first):")?;
+        let mut i = 0;
+        while i < self.trace.len() {
+            let entry = &self.trace[i];
+            write!(f, "\n    in {}, called at line:{}, column:{}", entry.function, entry.row, entry.col)?;
+            // Deep recursion repeats the same entry, print it only once.
+            let repeated = self.trace[i + 1..].iter().take_while(|e| *e == entry).count();
+            if repeated > 0 {
+                write!(f, "\n    ... previous call repeated {} more times", repeated)?;
+            }
+            i += repeated + 1;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,220 @@
+use super::parser::Parser;
+use super::tokenizer::{Token, TokenType, Tokenizer};
+
+const INDENT: &str = "    ";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bracket {
+    Block,
+    Map,
+    Paren,
+    Square,
+}
+
+/// Re-emits a token stream in the canonical style: one space around binary
+/// operators and after `,` and `:`, braces of blocks on their own line as in
+/// `language.txt`, four spaces of indentation per block, at most one blank
+/// line between statements. Line breaks inside brackets are kept.
+struct Formatter {
+    lines: Vec<String>,
+    line: String,
+    depth: usize,
+    /// Open brackets with the index of the line they were opened on.
+    stack: Vec<(Bracket, usize)>,
+    /// Line breaks since the last token.
+    newlines: usize,
+    /// The next token starts a new line.
+    force_break: bool,
+    prev: Option<TokenType>,
+    /// The previous token ends an operand, so `(` and `[` after it are a call
+    /// or an index and a `-` is binary.
+    prev_operand: bool,
+    prev_unary: bool,
+    prev_label: bool,
+    /// `while`, `for`, `func` and `operator` seen whose block has not been
+    /// opened yet, any other `{` starts a map.
+    pending_blocks: usize,
+}
+
+/// Quotes a string literal, escaping what the tokenizer unescapes.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl Formatter {
+    fn new() -> Formatter {
+        Formatter {
+            lines: Vec::new(),
+            line: String::new(),
+            depth: 0,
+            stack: Vec::new(),
+            newlines: 0,
+            force_break: false,
+            prev: None,
+            prev_operand: false,
+            prev_unary: false,
+            prev_label: false,
+            pending_blocks: 0,
+        }
+    }
+
+    /// Statements are only separated by line breaks outside of brackets.
+    fn in_block(&self) -> bool {
+        matches!(self.stack.last(), None | Some((Bracket::Block, _)))
+    }
+
+    /// Brackets opened on the same line add a single level of indentation.
+    fn open_depth(&self) -> usize {
+        let mut depth = 0;
+        let mut last = None;
+        for (_, line) in &self.stack {
+            if last != Some(line) {
+                depth += 1;
+                last = Some(line);
+            }
+        }
+        depth
+    }
+
+    fn begin_line(&mut self, blank: bool) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.lines.push(format!("{}{}", INDENT.repeat(self.depth), line));
+        }
+        let after_open = match self.lines.last() {
+            Some(last) => last.trim().is_empty() || last.trim() == "{",
+            None => true,
+        };
+        if blank && !after_open {
+            self.lines.push(String::new());
+        }
+        self.depth = self.open_depth();
+        self.force_break = false;
+    }
+
+    fn space_before(&self, token_type: TokenType) -> bool {
+        let prev = match self.prev {
+            Some(prev) => prev,
+            None => return false,
+        };
+        match (prev, token_type) {
+            (_, TokenType::Comma) | (_, TokenType::Colon) | (_, TokenType::Dot) |
+            (_, TokenType::RP) | (_, TokenType::RSquare) | (_, TokenType::RBraceket) => false,
+            (TokenType::LP, _) | (TokenType::LSquare, _) | (TokenType::LBraceket, _) |
+            (TokenType::Dot, _) | (TokenType::Ellipsis, _) => false,
+            (TokenType::DotDot, _) | (TokenType::DotDotEq, _) |
+            (_, TokenType::DotDot) | (_, TokenType::DotDotEq) => false,
+            (TokenType::Integer, TokenType::LP) => true,
+            (_, TokenType::LP) | (_, TokenType::LSquare) => !self.prev_operand,
+            // `--a` would lex as one token once `--` is declared.
+            (TokenType::Sub, TokenType::Sub) => true,
+            _ => !self.prev_unary,
+        }
+    }
+
+    fn comment(&mut self, token: &Token) {
+        if !self.line.is_empty() && self.newlines == 0 {
+            self.line.push(' ');
+        } else {
+            let blank = self.newlines >= 2 && self.in_block();
+            self.begin_line(blank);
+        }
+        self.line.push_str(token.literal.trim_end());
+        self.newlines = 0;
+        self.force_break = true;
+    }
+
+    fn token(&mut self, token: &Token) {
+        let token_type = token.token_type;
+        match token_type {
+            TokenType::Eof => return,
+            TokenType::Newline => {
+                self.newlines += 1;
+                return;
+            },
+            TokenType::Comment => return self.comment(token),
+            _ => (),
+        }
+        let opens_block = token_type == TokenType::LBraceket && self.prev_operand && self.pending_blocks > 0;
+        match token_type {
+            TokenType::While | TokenType::For | TokenType::FuncDecl | TokenType::Operator => self.pending_blocks += 1,
+            _ if opens_block => self.pending_blocks -= 1,
+            _ => (),
+        }
+        // A line break in a block ends the statement, a `-` starting the
+        // next one is a prefix operator.
+        if self.newlines > 0 && self.in_block() && !self.prev_label {
+            self.prev_operand = false;
+        }
+        let closed = match token_type {
+            TokenType::RP | TokenType::RSquare | TokenType::RBraceket => self.stack.pop().map(|(bracket, _)| bracket),
+            _ => None,
+        };
+        if opens_block || closed == Some(Bracket::Block) {
+            self.begin_line(false);
+        } else if !self.line.is_empty() && (self.force_break || (self.newlines > 0 && !self.prev_label)) {
+            let blank = self.newlines >= 2 && self.in_block();
+            self.begin_line(blank);
+        } else if !self.line.is_empty() && self.space_before(token_type) {
+            self.line.push(' ');
+        }
+        match token_type {
+            TokenType::Str => self.line.push_str(&quote(&token.literal)),
+            _ => self.line.push_str(&token.literal),
+        }
+        let opened = match token_type {
+            TokenType::LBraceket if opens_block => Some(Bracket::Block),
+            TokenType::LBraceket => Some(Bracket::Map),
+            TokenType::LP => Some(Bracket::Paren),
+            TokenType::LSquare => Some(Bracket::Square),
+            _ => None,
+        };
+        if let Some(bracket) = opened {
+            self.stack.push((bracket, self.lines.len()));
+        }
+        self.force_break = opens_block;
+        self.prev_unary = token_type == TokenType::Sub && !self.prev_operand;
+        self.prev_operand = match token_type {
+            // A block closed inside an expression is the body of a lambda.
+            TokenType::Symbol | TokenType::Integer | TokenType::Str |
+            TokenType::RP | TokenType::RSquare | TokenType::RBraceket => true,
+            _ => false,
+        };
+        self.prev_label = token_type == TokenType::Colon && self.in_block();
+        self.prev = Some(token_type);
+        self.newlines = 0;
+    }
+
+    fn finish(mut self) -> String {
+        self.begin_line(false);
+        let mut text = self.lines.join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Formats source code, comments included. Code with syntax errors is not
+/// formatted, the error is returned instead.
+pub fn format(source: &str) -> Result<String, String> {
+    Parser::new(Tokenizer::new(source)).parse()?;
+    let mut tokenizer = Tokenizer::new(source);
+    tokenizer.keep_comments();
+    let mut formatter = Formatter::new();
+    for token in tokenizer.tokens()? {
+        formatter.token(&token);
+    }
+    Ok(formatter.finish())
+}
@@ -0,0 +1,717 @@
+//! Parser generator. Reads a grammar written in the notation of
+//! `language.txt`, checks that it is LL(1) and generates the token and
+//! node definitions plus a table driven parser for it.
+//!
+//! ```text
+//! # comment
+//! INTEGER := "[0-9]+"                  token, the string is a regular expression
+//! STATEMENT := "print" EXPRESSION      rule, strings are literal tokens
+//!           += NAME "=" EXPRESSION     another alternative
+//! LIST := "[" [ITEMS] "]"              [...] optional, {...} repeated
+//! RANGE := SUM [("..", "..=") SUM]     (a, b) is a choice
+//! ```
+//!
+//! A rule whose whole body is a single string defines a token. Other rules
+//! refer to it by name, or by a string equal to its pattern when the pattern
+//! has no regular expression operators, e.g. `"print"` for `PRINT := "print"`.
+//!
+//! `build.rs` generates the parser of the interpreter from `grammar.txt`
+//! with this module, `src/parser.rs` turns the trees it builds into the AST.
+//! The tokenizer takes its token definitions from the same file.
+//! `freestyle compile` generates the parser for any grammar file.
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+/// Characters with a meaning in token patterns.
+const REGEX_CHARS: &str = "\\[]()|*+?.";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Symbol {
+    Terminal(usize),
+    Nonterminal(usize),
+}
+
+pub struct TokenDef {
+    pub name: String,
+    /// Regular expression for defined tokens, the literal text for tokens
+    /// that only appear as strings in rules.
+    pub pattern: String,
+    pub literal: bool,
+}
+
+pub struct Nonterminal {
+    pub name: String,
+    /// Written in the grammar file, helpers for `[...]`, `{...}` and
+    /// `(...)` are not and get no node type.
+    pub user: bool,
+}
+
+pub struct Production {
+    pub lhs: usize,
+    pub rhs: Vec<Symbol>,
+}
+
+pub struct Grammar {
+    /// The last token is the end of file marker.
+    pub tokens: Vec<TokenDef>,
+    pub nonterminals: Vec<Nonterminal>,
+    pub productions: Vec<Production>,
+    /// The first rule of the file.
+    pub start: usize,
+}
+
+/// Result of the LL(1) analysis.
+pub struct Table {
+    /// Tokens that can start each nonterminal.
+    pub first: Vec<BTreeSet<usize>>,
+    /// Tokens that can follow each nonterminal, `EOF` after the start rule.
+    pub follow: Vec<BTreeSet<usize>>,
+    /// Production to expand a nonterminal with, by lookahead token.
+    pub predict: BTreeMap<(usize, usize), usize>,
+    pub conflicts: Vec<String>,
+}
+
+enum Expr {
+    Literal(String, u32),
+    Name(String, u32),
+    Seq(Vec<Expr>),
+    Choice(Vec<Expr>),
+    Optional(Box<Expr>),
+    Repeat(Box<Expr>),
+}
+
+#[derive(Clone, PartialEq)]
+enum Word {
+    Name(String),
+    Str(String),
+    Define,
+    Alternative,
+    Punct(char),
+}
+
+/// One rule of the grammar file with the alternatives of its `+=` lines
+/// joined into a single body.
+struct RuleText {
+    name: String,
+    row: u32,
+    body: Vec<Word>,
+}
+
+fn split_words(row: u32, line: &str) -> Result<Vec<Word>, String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut words = Vec::<Word>::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            words.push(Word::Name(chars[start..pos].iter().collect()));
+        } else if c == '"' {
+            // Escapes are kept, token patterns need them.
+            let mut text = String::new();
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    None => return Err(format!("line:{}, grammar error, unterminated string", row)),
+                    Some('"') => break,
+                    Some('\\') if pos + 1 < chars.len() => {
+                        text.push('\\');
+                        text.push(chars[pos + 1]);
+                        pos += 2;
+                    },
+                    Some(c) => {
+                        text.push(*c);
+                        pos += 1;
+                    },
+                }
+            }
+            pos += 1;
+            words.push(Word::Str(text));
+        } else if c == ':' && chars.get(pos + 1) == Some(&'=') {
+            words.push(Word::Define);
+            pos += 2;
+        } else if c == '+' && chars.get(pos + 1) == Some(&'=') {
+            words.push(Word::Alternative);
+            pos += 2;
+        } else if "[]{}(),".contains(c) {
+            words.push(Word::Punct(c));
+            pos += 1;
+        } else {
+            return Err(format!("line:{}, grammar error, unexpected character '{}'", row, c));
+        }
+    }
+    Ok(words)
+}
+
+fn read_rules(text: &str) -> Result<Vec<RuleText>, String> {
+    let mut rules = Vec::<RuleText>::new();
+    for (i, line) in text.lines().enumerate() {
+        let row = i as u32 + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let mut words = split_words(row, trimmed)?;
+        match (words.first(), words.get(1)) {
+            (Some(Word::Name(name)), Some(Word::Define)) => {
+                let name = name.clone();
+                words.drain(..2);
+                rules.push(RuleText { name, row, body: words });
+            },
+            (Some(Word::Alternative), _) => match rules.last_mut() {
+                Some(rule) => rule.body.extend(words),
+                None => return Err(format!("line:{}, grammar error, '+=' before the first rule", row)),
+            },
+            _ => return Err(format!("line:{}, grammar error, expect 'NAME := ...' or '+= ...'", row)),
+        }
+    }
+    Ok(rules)
+}
+
+/// Recursive descent over the words of a rule body.
+struct BodyParser<'a> {
+    words: &'a [Word],
+    pos: usize,
+    row: u32,
+}
+
+impl<'a> BodyParser<'a> {
+    fn peek(&self) -> Option<&Word> {
+        self.words.get(self.pos)
+    }
+
+    /// `,` separates alternatives only inside parentheses.
+    fn choice(&mut self, comma: bool) -> Result<Expr, String> {
+        let mut alternatives = vec![self.sequence()?];
+        loop {
+            match self.peek() {
+                Some(Word::Alternative) => (),
+                Some(Word::Punct(',')) if comma => (),
+                _ => break,
+            }
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        if alternatives.len() == 1 {
+            return Ok(alternatives.pop().unwrap());
+        }
+        Ok(Expr::Choice(alternatives))
+    }
+
+    fn sequence(&mut self) -> Result<Expr, String> {
+        let mut items = Vec::<Expr>::new();
+        loop {
+            let item = match self.peek() {
+                Some(Word::Name(name)) => Expr::Name(name.clone(), self.row),
+                Some(Word::Str(text)) => Expr::Literal(text.clone(), self.row),
+                Some(Word::Punct('[')) => Expr::Optional(Box::new(self.group(']', false)?)),
+                Some(Word::Punct('{')) => Expr::Repeat(Box::new(self.group('}', false)?)),
+                Some(Word::Punct('(')) => self.group(')', true)?,
+                _ => break,
+            };
+            if let Expr::Name(..) | Expr::Literal(..) = item {
+                self.pos += 1;
+            }
+            items.push(item);
+        }
+        if items.len() == 1 {
+            return Ok(items.pop().unwrap());
+        }
+        Ok(Expr::Seq(items))
+    }
+
+    fn group(&mut self, close: char, comma: bool) -> Result<Expr, String> {
+        self.pos += 1;
+        let inner = self.choice(comma)?;
+        if self.peek() != Some(&Word::Punct(close)) {
+            return Err(format!("line:{}, grammar error, expect '{}'", self.row, close));
+        }
+        self.pos += 1;
+        Ok(inner)
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(c) => result.push(c),
+                None => result.push('\\'),
+            },
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// The text a pattern matches when it has no regular expression operators.
+pub fn plain_pattern(pattern: &str) -> Option<String> {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if REGEX_CHARS.contains(c) {
+            return None;
+        }
+    }
+    Some(unescape(pattern))
+}
+
+fn char_name(c: char) -> Option<&'static str> {
+    let name = match c {
+        '(' => "LParen", ')' => "RParen", '[' => "LSquare", ']' => "RSquare",
+        '{' => "LBrace", '}' => "RBrace", ',' => "Comma", ':' => "Colon",
+        ';' => "Semicolon", '.' => "Dot", '=' => "Equal", '<' => "Less",
+        '>' => "Greater", '!' => "Bang", '+' => "Plus", '-' => "Minus",
+        '*' => "Star", '/' => "Slash", '%' => "Percent", '^' => "Caret",
+        '&' => "Amp", '|' => "Pipe", '~' => "Tilde", '?' => "Question",
+        '@' => "At", '$' => "Dollar", '#' => "Hash",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// `LIST_ITEMS` -> `ListItems`
+fn camel_case(name: &str) -> String {
+    name.split('_').filter(|part| !part.is_empty()).map(|part| {
+        let mut chars = part.chars();
+        let first = chars.next().unwrap();
+        first.to_uppercase().chain(chars.flat_map(|c| c.to_lowercase())).collect::<String>()
+    }).collect()
+}
+
+/// Token name for a string used in a rule: keywords are capitalized,
+/// punctuation is spelled out, `"..="` is `DOT_DOT_EQUAL`.
+fn literal_token_name(text: &str) -> Option<String> {
+    if text.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Some(text.to_uppercase());
+    }
+    let names: Option<Vec<&str>> = text.chars().map(char_name).collect();
+    names.map(|names| names.iter().map(|name| camel_to_upper(name)).collect::<Vec<String>>().join("_"))
+}
+
+fn camel_to_upper(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            result.push('_');
+        }
+        result.push(c.to_ascii_uppercase());
+    }
+    result
+}
+
+struct Builder {
+    grammar: Grammar,
+    /// Rules by name, for checking references.
+    rules: BTreeMap<String, usize>,
+    defined: BTreeMap<String, usize>,
+    helpers: usize,
+}
+
+impl Builder {
+    fn terminal_for_literal(&mut self, text: &str, row: u32) -> Result<usize, String> {
+        let literal = unescape(text);
+        if let Some(index) = self.grammar.tokens.iter().position(|token|
+                plain_pattern(&token.pattern).as_deref() == Some(literal.as_str()) || (token.literal && token.pattern == literal)) {
+            return Ok(index);
+        }
+        let name = match literal_token_name(&literal) {
+            Some(name) if !name.is_empty() => name,
+            _ => return Err(format!("line:{}, grammar error, can not name token \"{}\", define it with a rule", row, text)),
+        };
+        if self.grammar.tokens.iter().any(|token| token.name == name) {
+            return Err(format!("line:{}, grammar error, token \"{}\" clashes with token {}", row, text, name));
+        }
+        self.grammar.tokens.push(TokenDef { name, pattern: literal, literal: true });
+        Ok(self.grammar.tokens.len() - 1)
+    }
+
+    fn helper(&mut self, owner: usize, kind: &str) -> usize {
+        self.helpers += 1;
+        let name = format!("{}.{}{}", self.grammar.nonterminals[owner].name, kind, self.helpers);
+        self.grammar.nonterminals.push(Nonterminal { name, user: false });
+        self.grammar.nonterminals.len() - 1
+    }
+
+    /// Appends the symbols for `expr` to `rhs`, introducing helper
+    /// nonterminals for nested choices, options and repetitions.
+    fn lower(&mut self, owner: usize, expr: &Expr, rhs: &mut Vec<Symbol>) -> Result<(), String> {
+        match expr {
+            Expr::Literal(text, row) => rhs.push(Symbol::Terminal(self.terminal_for_literal(text, *row)?)),
+            Expr::Name(name, row) => match (self.defined.get(name), self.rules.get(name)) {
+                (Some(token), _) => rhs.push(Symbol::Terminal(*token)),
+                (_, Some(rule)) => rhs.push(Symbol::Nonterminal(*rule)),
+                _ => return Err(format!("line:{}, grammar error, undefined symbol {} in rule {}",
+                    row, name, self.grammar.nonterminals[owner].name)),
+            },
+            Expr::Seq(items) => {
+                for item in items {
+                    self.lower(owner, item, rhs)?;
+                }
+            },
+            Expr::Choice(alternatives) => {
+                let helper = self.helper(owner, "group");
+                for alternative in alternatives {
+                    self.add_production(owner, helper, alternative)?;
+                }
+                rhs.push(Symbol::Nonterminal(helper));
+            },
+            Expr::Optional(inner) => {
+                let helper = self.helper(owner, "opt");
+                self.add_production(owner, helper, inner)?;
+                self.grammar.productions.push(Production { lhs: helper, rhs: Vec::new() });
+                rhs.push(Symbol::Nonterminal(helper));
+            },
+            Expr::Repeat(inner) => {
+                // X.repeat := inner X.repeat | (empty)
+                let helper = self.helper(owner, "repeat");
+                let mut body = Vec::new();
+                self.lower(owner, inner, &mut body)?;
+                body.push(Symbol::Nonterminal(helper));
+                self.grammar.productions.push(Production { lhs: helper, rhs: body });
+                self.grammar.productions.push(Production { lhs: helper, rhs: Vec::new() });
+                rhs.push(Symbol::Nonterminal(helper));
+            },
+        }
+        Ok(())
+    }
+
+    fn add_production(&mut self, owner: usize, lhs: usize, expr: &Expr) -> Result<(), String> {
+        let mut rhs = Vec::new();
+        self.lower(owner, expr, &mut rhs)?;
+        self.grammar.productions.push(Production { lhs, rhs });
+        Ok(())
+    }
+}
+
+impl Grammar {
+    pub fn parse(text: &str) -> Result<Grammar, String> {
+        let mut builder = Builder {
+            grammar: Grammar { tokens: Vec::new(), nonterminals: Vec::new(), productions: Vec::new(), start: 0 },
+            rules: BTreeMap::new(),
+            defined: BTreeMap::new(),
+            helpers: 0,
+        };
+        let mut bodies = Vec::<(usize, Expr)>::new();
+        for rule in read_rules(text)? {
+            if builder.rules.contains_key(&rule.name) || builder.defined.contains_key(&rule.name) {
+                return Err(format!("line:{}, grammar error, {} is already defined", rule.row, rule.name));
+            }
+            if let [Word::Str(pattern)] = rule.body.as_slice() {
+                builder.grammar.tokens.push(TokenDef { name: rule.name.clone(), pattern: pattern.clone(), literal: false });
+                builder.defined.insert(rule.name, builder.grammar.tokens.len() - 1);
+                continue;
+            }
+            let mut parser = BodyParser { words: &rule.body, pos: 0, row: rule.row };
+            let body = parser.choice(false)?;
+            if parser.pos != rule.body.len() {
+                return Err(format!("line:{}, grammar error, unexpected symbol in rule {}", rule.row, rule.name));
+            }
+            builder.grammar.nonterminals.push(Nonterminal { name: rule.name.clone(), user: true });
+            builder.rules.insert(rule.name, builder.grammar.nonterminals.len() - 1);
+            bodies.push((builder.grammar.nonterminals.len() - 1, body));
+        }
+        if bodies.is_empty() {
+            return Err("grammar error, no rules".to_string());
+        }
+        for (rule, body) in &bodies {
+            match body {
+                Expr::Choice(alternatives) => for alternative in alternatives {
+                    builder.add_production(*rule, *rule, alternative)?;
+                },
+                _ => builder.add_production(*rule, *rule, body)?,
+            }
+        }
+        builder.grammar.tokens.push(TokenDef { name: "EOF".to_string(), pattern: String::new(), literal: false });
+        Ok(builder.grammar)
+    }
+
+    pub fn eof(&self) -> usize {
+        self.tokens.len() - 1
+    }
+
+    fn symbol_text(&self, symbol: &Symbol) -> String {
+        match symbol {
+            Symbol::Terminal(t) if self.tokens[*t].literal => format!("{:?}", self.tokens[*t].pattern),
+            Symbol::Terminal(t) => self.tokens[*t].name.clone(),
+            Symbol::Nonterminal(n) => self.nonterminals[*n].name.clone(),
+        }
+    }
+
+    pub fn production_text(&self, index: usize) -> String {
+        let production = &self.productions[index];
+        let rhs: Vec<String> = production.rhs.iter().map(|symbol| self.symbol_text(symbol)).collect();
+        let rhs = if rhs.is_empty() { "(empty)".to_string() } else { rhs.join(" ") };
+        format!("{} := {}", self.nonterminals[production.lhs].name, rhs)
+    }
+
+    fn nullable(&self) -> Vec<bool> {
+        let mut nullable = vec![false; self.nonterminals.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                if !nullable[production.lhs] && production.rhs.iter().all(|symbol|
+                        matches!(symbol, Symbol::Nonterminal(n) if nullable[*n])) {
+                    nullable[production.lhs] = true;
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+
+    /// FIRST of a symbol string, and whether the whole string is nullable.
+    fn first_of(symbols: &[Symbol], first: &[BTreeSet<usize>], nullable: &[bool]) -> (BTreeSet<usize>, bool) {
+        let mut result = BTreeSet::new();
+        for symbol in symbols {
+            match symbol {
+                Symbol::Terminal(t) => {
+                    result.insert(*t);
+                    return (result, false);
+                },
+                Symbol::Nonterminal(n) => {
+                    result.extend(&first[*n]);
+                    if !nullable[*n] {
+                        return (result, false);
+                    }
+                },
+            }
+        }
+        (result, true)
+    }
+
+    /// Rules that can derive themselves as their leftmost symbol.
+    fn left_recursive(&self, nullable: &[bool]) -> Vec<usize> {
+        let mut leftmost = vec![BTreeSet::<usize>::new(); self.nonterminals.len()];
+        for production in &self.productions {
+            for symbol in &production.rhs {
+                match symbol {
+                    Symbol::Terminal(_) => break,
+                    Symbol::Nonterminal(n) => {
+                        leftmost[production.lhs].insert(*n);
+                        if !nullable[*n] {
+                            break;
+                        }
+                    },
+                }
+            }
+        }
+        (0..self.nonterminals.len()).filter(|start| {
+            let mut seen = BTreeSet::new();
+            let mut stack: Vec<usize> = leftmost[*start].iter().copied().collect();
+            while let Some(n) = stack.pop() {
+                if n == *start {
+                    return true;
+                }
+                if seen.insert(n) {
+                    stack.extend(&leftmost[n]);
+                }
+            }
+            false
+        }).collect()
+    }
+
+    /// Builds the LL(1) parse table, every lookahead token that selects
+    /// more than one production is reported as a conflict.
+    pub fn analyze(&self) -> Table {
+        let nullable = self.nullable();
+        let mut first = vec![BTreeSet::<usize>::new(); self.nonterminals.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                let (symbols, _) = Grammar::first_of(&production.rhs, &first, &nullable);
+                let before = first[production.lhs].len();
+                first[production.lhs].extend(symbols);
+                changed |= first[production.lhs].len() != before;
+            }
+        }
+        let mut follow = vec![BTreeSet::<usize>::new(); self.nonterminals.len()];
+        follow[self.start].insert(self.eof());
+        changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                for (i, symbol) in production.rhs.iter().enumerate() {
+                    if let Symbol::Nonterminal(n) = symbol {
+                        let (mut symbols, rest_nullable) = Grammar::first_of(&production.rhs[i + 1..], &first, &nullable);
+                        if rest_nullable {
+                            symbols.extend(follow[production.lhs].clone());
+                        }
+                        let before = follow[*n].len();
+                        follow[*n].extend(symbols);
+                        changed |= follow[*n].len() != before;
+                    }
+                }
+            }
+        }
+        let mut table = Table { first, follow, predict: BTreeMap::new(), conflicts: Vec::new() };
+        for n in self.left_recursive(&nullable) {
+            table.conflicts.push(format!("rule {} is left recursive", self.nonterminals[n].name));
+        }
+        for (index, production) in self.productions.iter().enumerate() {
+            let (mut lookahead, rhs_nullable) = Grammar::first_of(&production.rhs, &table.first, &nullable);
+            if rhs_nullable {
+                lookahead.extend(&table.follow[production.lhs]);
+            }
+            for t in lookahead {
+                match table.predict.get(&(production.lhs, t)) {
+                    Some(other) => table.conflicts.push(format!("LL(1) conflict in {} on {}:\n    {}\n    {}",
+                        self.nonterminals[production.lhs].name, self.symbol_text(&Symbol::Terminal(t)),
+                        self.production_text(*other), self.production_text(index))),
+                    None => {
+                        table.predict.insert((production.lhs, t), index);
+                    },
+                }
+            }
+        }
+        table
+    }
+
+    /// Rust source with `TokenType`, `NodeType`, the parse table and a
+    /// parser driven by it.
+    pub fn generate(&self, table: &Table) -> String {
+        let mut out = String::new();
+        out.push_str("// Generated by `freestyle compile`, do not edit.\n\n");
+        out.push_str("#[derive(Copy, Clone, PartialEq, Debug)]\npub enum TokenType {\n");
+        for token in &self.tokens {
+            out.push_str(&format!("    {},\n", camel_case(&token.name)));
+        }
+        out.push_str("}\n\n");
+        out.push_str(&format!("/// Every token type, in the order of the grammar file.\npub const TOKENS: [TokenType; {}] = [\n", self.tokens.len()));
+        for token in &self.tokens {
+            out.push_str(&format!("    TokenType::{},\n", camel_case(&token.name)));
+        }
+        out.push_str("];\n\n");
+        out.push_str("/// Name of the token in the grammar file.\n");
+        out.push_str("pub fn name(token: TokenType) -> &'static str {\n    match token {\n");
+        for token in &self.tokens {
+            out.push_str(&format!("        TokenType::{} => {:?},\n", camel_case(&token.name), token.name));
+        }
+        out.push_str("    }\n}\n\n#[derive(Copy, Clone, PartialEq, Debug)]\npub enum NodeType {\n");
+        for nonterminal in self.nonterminals.iter().filter(|n| n.user) {
+            out.push_str(&format!("    {},\n", camel_case(&nonterminal.name)));
+        }
+        out.push_str("}\n\n");
+        out.push_str("/// Literal text of the tokens that only appear as strings in the grammar.\n");
+        out.push_str("pub fn literal(token: TokenType) -> Option<&'static str> {\n    match token {\n");
+        for token in self.tokens.iter().filter(|token| token.literal) {
+            out.push_str(&format!("        TokenType::{} => Some({:?}),\n", camel_case(&token.name), token.pattern));
+        }
+        out.push_str("        _ => None,\n    }\n}\n\n");
+        out.push_str("/// Patterns of the tokens defined by a rule of their own.\n");
+        out.push_str("pub fn pattern(token: TokenType) -> Option<&'static str> {\n    match token {\n");
+        for token in self.tokens.iter().filter(|token| !token.literal && !token.pattern.is_empty()) {
+            out.push_str(&format!("        TokenType::{} => Some({:?}),\n", camel_case(&token.name), token.pattern));
+        }
+        out.push_str("        _ => None,\n    }\n}\n\n");
+        out.push_str("#[derive(Copy, Clone)]\nenum Symbol {\n    Token(TokenType),\n    Rule(usize),\n}\n\n");
+        out.push_str("/// Node type of every rule, helper rules are inlined into their parent.\n");
+        out.push_str(&format!("const RULES: [Option<NodeType>; {}] = [\n", self.nonterminals.len()));
+        for nonterminal in &self.nonterminals {
+            match nonterminal.user {
+                true => out.push_str(&format!("    Some(NodeType::{}),\n", camel_case(&nonterminal.name))),
+                false => out.push_str(&format!("    None, // {}\n", nonterminal.name)),
+            }
+        }
+        out.push_str("];\n\n");
+        out.push_str(&format!("const PRODUCTIONS: [&[Symbol]; {}] = [\n", self.productions.len()));
+        for (index, production) in self.productions.iter().enumerate() {
+            let rhs: Vec<String> = production.rhs.iter().map(|symbol| match symbol {
+                Symbol::Terminal(t) => format!("Symbol::Token(TokenType::{})", camel_case(&self.tokens[*t].name)),
+                Symbol::Nonterminal(n) => format!("Symbol::Rule({})", n),
+            }).collect();
+            out.push_str(&format!("    // {}\n    &[{}],\n", self.production_text(index), rhs.join(", ")));
+        }
+        out.push_str("];\n\n");
+        out.push_str("/// The production to expand `rule` with when `token` is next.\n");
+        out.push_str("fn predict(rule: usize, token: TokenType) -> Option<usize> {\n    match (rule, token) {\n");
+        for ((n, t), production) in &table.predict {
+            out.push_str(&format!("        ({}, TokenType::{}) => Some({}),\n", n, camel_case(&self.tokens[*t].name), production));
+        }
+        out.push_str("        _ => None,\n    }\n}\n\n");
+        out.push_str(DRIVER.replace("START", &self.start.to_string()).as_str());
+        out
+    }
+}
+
+const DRIVER: &str = "pub enum Child {
+    /// Index of the token in the input.
+    Token(usize),
+    Node(Node),
+}
+
+pub struct Node {
+    pub node_type: NodeType,
+    pub childs: Vec<Child>,
+}
+
+/// Where the input stops following the grammar.
+pub struct SyntaxError {
+    /// Index of the unexpected token in the input.
+    pub pos: usize,
+    /// The tokens the grammar allows there.
+    pub expected: Vec<TokenType>,
+}
+
+fn expand(mut rule: usize, tokens: &[TokenType], pos: &mut usize, childs: &mut Vec<Child>) -> Result<(), SyntaxError> {
+    loop {
+        let production = match predict(rule, tokens[*pos]) {
+            Some(production) => production,
+            None => return Err(SyntaxError {
+                pos: *pos,
+                expected: TOKENS.iter().copied().filter(|token| predict(rule, *token).is_some()).collect(),
+            }),
+        };
+        // A helper rule at the end, such as the rest of a repetition, is
+        // expanded in place so that long inputs don't recurse deeply.
+        let (symbols, next) = match PRODUCTIONS[production].split_last() {
+            Some((Symbol::Rule(last), symbols)) if RULES[*last].is_none() => (symbols, Some(*last)),
+            _ => (PRODUCTIONS[production], None),
+        };
+        for symbol in symbols {
+            match *symbol {
+                Symbol::Token(token) if tokens[*pos] == token => {
+                    childs.push(Child::Token(*pos));
+                    *pos += 1;
+                },
+                Symbol::Token(token) => return Err(SyntaxError { pos: *pos, expected: vec![token] }),
+                Symbol::Rule(inner) => match RULES[inner] {
+                    Some(node_type) => {
+                        let mut node = Node { node_type, childs: Vec::new() };
+                        expand(inner, tokens, pos, &mut node.childs)?;
+                        childs.push(Child::Node(node));
+                    },
+                    None => expand(inner, tokens, pos, childs)?,
+                },
+            }
+        }
+        match next {
+            Some(next) => rule = next,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Parses `tokens`, which must end with `TokenType::Eof`.
+pub fn parse(tokens: &[TokenType]) -> Result<Node, SyntaxError> {
+    let mut root = Node { node_type: RULES[START].unwrap(), childs: Vec::new() };
+    let mut pos = 0;
+    expand(START, tokens, &mut pos, &mut root.childs)?;
+    match tokens[pos] {
+        TokenType::Eof => Ok(root),
+        _ => Err(SyntaxError { pos, expected: vec![TokenType::Eof] }),
+    }
+}
+";
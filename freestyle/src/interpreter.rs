@@ -1,161 +1,560 @@
-use super::ast;
-use super::ntable;
-use super::tokenizer;
-use std::collections::HashMap;
-use std::vec;
-
-pub struct Interpreter {
-    name_table: ntable::NameTable,
-}
-
-impl Interpreter {
-    fn exec_func_call(&self, node: &ast::AstNode, func_table: &HashMap<String, &ast::AstNode>) -> Result<i64, String> {
-        let mut local_name_table = vec::Vec::<ntable::Variable>::new();
-        for param in &node.childs[0].childs {
-            let variable = match self.name_table.get(&param.token.literal) {
-                Ok(variable) => variable,
-                Err(msg) => return Err(msg),
-            };
-            local_name_table.push(variable);
-        }
-        let func_node = match func_table.get(&node.token.literal) {
-            Some(node) => node,
-            None => return Err("function not defined".to_string()),
-        };
-        for i in 0..func_node.childs[0].childs.len() {
-            local_name_table[i].name = func_node.childs[0].childs[i].token.literal.clone();
-        }
-        //Seems not possible to finish this without scope system
-        Ok(0)
-    }
-    fn lookup_variable(&self, node: &ast::AstNode) -> Result<i64, String> {
-        return match self.name_table.get(&node.token.literal) {
-            Ok(variable) => Ok(variable.value),
-            Err(msg) => Err(msg),
-        };
-    }
-
-    fn exec_pow_expression(&self, node: &ast::AstNode, func_table: &HashMap<String, &ast::AstNode>) -> Result<i64, String> {
-        let left = match self.exec_expression(&node.childs[0], func_table) {
-            Ok(value) => value,
-            Err(msg) => return Err(msg),
-        };
-        let right = match self.exec_expression(&node.childs[1], func_table) {
-            Ok(value) => value,
-            Err(msg) => return Err(msg),
-        };
-        match node.token.token_type {
-            tokenizer::TokenType::Pow => {
-                let mut result: i64 = 1;
-                for _ in 0..right {
-                    result *= left;
-                }
-                return Ok(result);
-            },
-            _ => panic!("logic error"),
-        }
-    }
-
-    fn exec_mul_expression(&self, node: &ast::AstNode, func_table: &HashMap<String, &ast::AstNode>) -> Result<i64, String> {
-        let left = match self.exec_expression(&node.childs[0], func_table) {
-            Ok(value) => value,
-            Err(msg) => return Err(msg),
-        };
-        let right = match self.exec_expression(&node.childs[1], func_table) {
-            Ok(value) => value,
-            Err(msg) => return Err(msg),
-        };
-        match node.token.token_type {
-            tokenizer::TokenType::Mul => return Ok(left * right),
-            tokenizer::TokenType::Div => return Ok(left / right),
-            _ => panic!("logic error"),
-        }
-    }
-
-    fn exec_add_expression(&self, node: &ast::AstNode, func_table: &HashMap<String, &ast::AstNode>) -> Result<i64, String> {
-        let left = match self.exec_expression(&node.childs[0], func_table) {
-            Ok(value) => value,
-            Err(msg) => return Err(msg),
-        };
-        let right = match self.exec_expression(&node.childs[1], func_table) {
-            Ok(value) => value,
-            Err(msg) => return Err(msg),
-        };
-        match node.token.token_type {
-            tokenizer::TokenType::Add => return Ok(left + right),
-            tokenizer::TokenType::Sub => return Ok(left - right),
-            tokenizer::TokenType::Mod => return Ok(left % right),
-            _ => panic!("logic error"),
-        }
-    }
-
-    fn exec_expression(&self, node: &ast::AstNode, func_table: &HashMap<String, &ast::AstNode>) -> Result<i64, String> {
-        match node.node_type {
-            ast::NodeType::Add => return self.exec_add_expression(node, func_table),
-            ast::NodeType::Mul => return self.exec_mul_expression(node, func_table),
-            ast::NodeType::Pow => return self.exec_pow_expression(node, func_table),
-            ast::NodeType::Integer => return match node.token.literal.parse::<i64>() {
-                Ok(value) => Ok(value),
-                Err(_) => Err(format!("lines:{}, column:{}, parse int failed", node.token.row, node.token.col)),
-            },
-            ast::NodeType::Name => return self.lookup_variable(node),
-            ast::NodeType::FuncCall => return self.exec_func_call(node, func_table),
-            _ => panic!("logic error"),
-        }
-    }
-
-    fn exec_print(&self, node: &ast::AstNode, func_table: &HashMap<String, &ast::AstNode>) -> Result<(), String> {
-        let expr = &node.childs[0];
-        let value = match self.exec_expression(expr, func_table) {
-            Ok(value) => value,
-            Err(msg) => return Err(msg),
-        };
-        println!("{}", value);
-        Ok(())
-    }
-
-    fn exec_assign(&mut self, node: &ast::AstNode, func_table: &HashMap<String, &ast::AstNode>) -> Result<(), String> {
-        let name = &node.childs[0];
-        let expr = &node.childs[1];
-        let value = match self.exec_expression(expr, func_table) {
-            Ok(value) => value,
-            Err(msg) => return Err(msg),
-        };
-        self.name_table.set(name.token.literal.clone(), ntable::Variable::new(&name.token.literal, value));
-        Ok(())
-    }
-
-    fn exec_func_decl<'a>(&mut self, node: &'a ast::AstNode, func_table: &mut HashMap<String, &'a ast::AstNode>) -> Result<(), String> {
-        func_table.insert(node.token.literal.clone(), node);
-        Ok(())
-    }
-
-    pub fn execute(&mut self, tree: &ast::AstNode) -> Result<(), String> {
-        let mut func_table = HashMap::<String, &ast::AstNode>::new();
-        for node in &tree.childs {
-            match node.node_type {
-                ast::NodeType::Print => match self.exec_print(node, &func_table) {
-                    Ok(()) => (),
-                    Err(msg) => return Err(msg),
-                },
-                ast::NodeType::Assign => match self.exec_assign(node, &func_table) {
-                    Ok(()) => (),
-                    Err(msg) => return Err(msg),
-                },
-                ast::NodeType::FuncDecl => match self.exec_func_decl(node, &mut func_table) {
-                    Ok(()) => (),
-                    Err(msg) => return Err(msg),
-                },
-                _ => return Err(format!("line:{}, column:{}, syntax error, expect 'print' or variable",
-                            node.token.row, node.token.col)),
-            }
-        }
-        Ok(())
-    }
-    
-    pub fn new() -> Interpreter {
-        Interpreter {
-            name_table: ntable::NameTable::new(),
-        }
-    }
-}
\ No newline at end of file
+use super::ast;
+use super::builtin;
+use super::error::{Error, TraceEntry};
+use super::ntable;
+use super::tokenizer;
+use super::value::{Function, Key, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// How control leaves a statement. Loops consume `Break`/`Continue` aimed at
+/// them (no label or their own label) and pass the rest outwards.
+enum Flow {
+    Normal,
+    Break(Option<String>),
+    Continue(Option<String>),
+    Return(Value),
+}
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+pub struct Interpreter {
+    globals: ntable::Env,
+    /// Functions implementing user defined infix operators.
+    operators: HashMap<String, Value>,
+    /// Scope of the code being executed, `globals` outside of functions.
+    env: ntable::Env,
+    call_depth: usize,
+    max_call_depth: usize,
+    /// Print the value of top-level expression statements, as the REPL does.
+    print_expressions: bool,
+}
+
+fn runtime_error(token: &tokenizer::Token, msg: &str) -> Error {
+    Error::new(format!("line:{}, column:{}, runtime error, {}", token.row, token.col, msg))
+}
+
+fn integer_operands(token: &tokenizer::Token, op: &ast::BinaryOp, left: &Value, right: &Value) -> Result<(i64, i64), Error> {
+    match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => Ok((*l, *r)),
+        _ => Err(runtime_error(token, &format!("unsupported operand types for '{}': '{}' and '{}'",
+            op.as_str(), left.type_name(), right.type_name()))),
+    }
+}
+
+impl Interpreter {
+    /// `break` and `continue` never leave a function body, the parser
+    /// rejects them outside of loops.
+    fn exec_func_body(&mut self, body: &[ast::Stmt]) -> Result<Value, Error> {
+        match self.exec_block(body)? {
+            Flow::Return(value) => Ok(value),
+            Flow::Normal | Flow::Break(_) | Flow::Continue(_) => Ok(Value::Nil),
+        }
+    }
+
+    /// Matches the arguments of a call to the parameters of `function`.
+    /// Returns one slot per parameter, `None` for parameters that take their
+    /// default value.
+    fn match_arguments(function: &Function, token: &tokenizer::Token, positional: Vec<Value>, named: Vec<(&tokenizer::Token, Value)>) -> Result<Vec<Option<Value>>, Error> {
+        let params = &function.def.params;
+        let variadic = matches!(params.last(), Some(ast::Param::Variadic { .. }));
+        let fixed = if variadic { params.len() - 1 } else { params.len() };
+        let mut slots: Vec<Option<Value>> = vec![None; params.len()];
+        let given = positional.len();
+        let mut positional = positional.into_iter();
+        for slot in slots.iter_mut().take(fixed) {
+            *slot = positional.next();
+        }
+        let extra: Vec<Value> = positional.collect();
+        if variadic {
+            slots[fixed] = Some(Value::new_list(extra));
+        } else if !extra.is_empty() {
+            let names: Vec<&str> = params.iter().map(|p| p.name().literal.as_str()).collect();
+            return Err(runtime_error(token, &format!("{}() takes at most {} positional arguments ({}) but {} were given",
+                function.name, fixed, names.join(", "), given)));
+        }
+        for (name, value) in named {
+            let position = params.iter().take(fixed).position(|p| p.name().literal == name.literal);
+            match position {
+                Some(i) if slots[i].is_some() => return Err(runtime_error(name, &format!(
+                    "{}() got multiple values for parameter '{}'", function.name, name.literal))),
+                Some(i) => slots[i] = Some(value),
+                None => return Err(runtime_error(name, &format!(
+                    "{}() has no parameter named '{}'", function.name, name.literal))),
+            }
+        }
+        let missing: Vec<String> = params.iter().zip(slots.iter())
+            .filter(|(param, slot)| slot.is_none() && matches!(param, ast::Param::Required { .. }))
+            .map(|(param, _)| format!("'{}'", param.name().literal))
+            .collect();
+        if !missing.is_empty() {
+            let what = if missing.len() == 1 { "argument for parameter" } else { "arguments for parameters" };
+            return Err(runtime_error(token, &format!("{}() missing {} {}",
+                function.name, what, missing.join(", "))));
+        }
+        Ok(slots)
+    }
+
+    /// Defines the parameters in the current scope. Defaults are evaluated
+    /// at call time and may refer to earlier parameters: `func f(a, b = a * 2)`.
+    fn bind_parameters(&mut self, function: &Function, slots: Vec<Option<Value>>) -> Result<(), Error> {
+        for (param, slot) in function.def.params.iter().zip(slots) {
+            let value = match (slot, param) {
+                (Some(value), _) => value,
+                (None, ast::Param::Optional { default, .. }) => self.exec_expression(default)?,
+                (None, _) => Value::Nil,
+            };
+            self.env.borrow_mut().define(&param.name().literal, value);
+        }
+        Ok(())
+    }
+
+    fn exec_func_call(&mut self, token: &tokenizer::Token, callee: &ast::Expr, args: &[ast::Arg]) -> Result<Value, Error> {
+        let callee = self.exec_expression(callee)?;
+        let mut positional = Vec::<Value>::new();
+        let mut named = Vec::<(&tokenizer::Token, Value)>::new();
+        for arg in args {
+            match arg {
+                ast::Arg::Positional(value) => positional.push(self.exec_expression(value)?),
+                ast::Arg::Named { name, value } => named.push((name, self.exec_expression(value)?)),
+            }
+        }
+        self.call_value(token, callee, positional, named)
+    }
+
+    /// Calls `callee`, `token` is the call site used for errors and traces.
+    fn call_value(&mut self, token: &tokenizer::Token, callee: Value, positional: Vec<Value>, named: Vec<(&tokenizer::Token, Value)>) -> Result<Value, Error> {
+        let function = match callee {
+            Value::Builtin(name) => {
+                if let Some((arg, _)) = named.first() {
+                    return Err(runtime_error(arg, &format!("{}() does not accept named arguments", name)));
+                }
+                return builtin::call(name, positional).map_err(|msg| runtime_error(token, &msg));
+            },
+            Value::Function(function) => function,
+            other => return Err(runtime_error(token, &format!("'{}' is not callable", other.type_name()))),
+        };
+        let slots = Interpreter::match_arguments(&function, token, positional, named)?;
+        if self.call_depth >= self.max_call_depth {
+            return Err(runtime_error(token, &format!("stack overflow at line {}, maximum call depth is {}",
+                token.row, self.max_call_depth)));
+        }
+        let locals = ntable::Environment::new_child(&function.env);
+        let caller_env = std::mem::replace(&mut self.env, locals);
+        self.call_depth += 1;
+        let result = self.bind_parameters(&function, slots)
+            .and_then(|_| self.exec_func_body(&function.def.body));
+        self.call_depth -= 1;
+        self.env = caller_env;
+        result.map_err(|mut err| {
+            err.trace.push(TraceEntry {
+                function: function.name.clone(),
+                row: token.row,
+                col: token.col,
+            });
+            err
+        })
+    }
+
+    fn lookup_variable(&self, token: &tokenizer::Token) -> Result<Value, Error> {
+        let value = self.env.borrow().lookup(&token.literal);
+        match value {
+            Ok(value) => Ok(value),
+            Err(_) if builtin::is_builtin(&token.literal) => Ok(Value::Builtin(builtin::name(&token.literal))),
+            Err(msg) => Err(runtime_error(token, &msg)),
+        }
+    }
+
+    fn set_variable(&mut self, name: &str, value: Value) {
+        ntable::Environment::assign(&self.env, name, value);
+    }
+
+    fn exec_arithmetic(token: &tokenizer::Token, op: &ast::BinaryOp, left: Value, right: Value) -> Result<Value, Error> {
+        if let (ast::BinaryOp::Add, Value::Str(l), Value::Str(r)) = (op, &left, &right) {
+            return Ok(Value::Str(format!("{}{}", l, r)));
+        }
+        let (left, right) = integer_operands(token, op, &left, &right)?;
+        let result = match op {
+            ast::BinaryOp::Add => left.checked_add(right),
+            ast::BinaryOp::Sub => left.checked_sub(right),
+            ast::BinaryOp::Mul => left.checked_mul(right),
+            ast::BinaryOp::Div | ast::BinaryOp::Mod if right == 0 => return Err(runtime_error(token, "division by zero")),
+            ast::BinaryOp::Div => left.checked_div(right),
+            ast::BinaryOp::Mod => left.checked_rem(right),
+            ast::BinaryOp::Range => return Ok(Value::Range(left, right)),
+            ast::BinaryOp::RangeInclusive => match right.checked_add(1) {
+                Some(end) => return Ok(Value::Range(left, end)),
+                None => None,
+            },
+            _ => match u32::try_from(right) {
+                Ok(exponent) => left.checked_pow(exponent),
+                Err(_) if right < 0 => return Err(runtime_error(token, "negative exponent")),
+                // Only 0, 1 and -1 survive an exponent this large.
+                Err(_) => match left {
+                    -1 => Some(if right % 2 == 0 { 1 } else { -1 }),
+                    0 | 1 => Some(left),
+                    _ => None,
+                },
+            },
+        };
+        match result {
+            Some(value) => Ok(Value::Integer(value)),
+            None => Err(runtime_error(token, "integer overflow")),
+        }
+    }
+
+    fn exec_compare(token: &tokenizer::Token, op: &ast::BinaryOp, left: Value, right: Value) -> Result<Value, Error> {
+        let ordering = match (&left, &right) {
+            (Value::Integer(l), Value::Integer(r)) => Some(l.cmp(r)),
+            (Value::Str(l), Value::Str(r)) => Some(l.cmp(r)),
+            _ => None,
+        };
+        let result = match (op, ordering) {
+            (ast::BinaryOp::Eq, _) => left.equals(&right),
+            (ast::BinaryOp::Ne, _) => !left.equals(&right),
+            (ast::BinaryOp::Lt, Some(ordering)) => ordering == Ordering::Less,
+            (ast::BinaryOp::Gt, Some(ordering)) => ordering == Ordering::Greater,
+            (ast::BinaryOp::Le, Some(ordering)) => ordering != Ordering::Greater,
+            (ast::BinaryOp::Ge, Some(ordering)) => ordering != Ordering::Less,
+            _ => return Err(runtime_error(token, &format!("unsupported operand types for '{}': '{}' and '{}'",
+                op.as_str(), left.type_name(), right.type_name()))),
+        };
+        Ok(Value::Bool(result))
+    }
+
+    /// `and`/`or` short-circuit and yield one of their operands.
+    fn exec_binary(&mut self, op: &ast::BinaryOp, token: &tokenizer::Token, lhs: &ast::Expr, rhs: &ast::Expr) -> Result<Value, Error> {
+        let left = self.exec_expression(lhs)?;
+        match (op, left.is_truthy()) {
+            (ast::BinaryOp::And, false) | (ast::BinaryOp::Or, true) => return Ok(left),
+            (ast::BinaryOp::And, true) | (ast::BinaryOp::Or, false) => return self.exec_expression(rhs),
+            _ => (),
+        }
+        let right = self.exec_expression(rhs)?;
+        match op {
+            ast::BinaryOp::Lt | ast::BinaryOp::Gt | ast::BinaryOp::Le |
+            ast::BinaryOp::Ge | ast::BinaryOp::Eq | ast::BinaryOp::Ne => Interpreter::exec_compare(token, op, left, right),
+            ast::BinaryOp::Custom(name) => self.exec_custom_op(token, name, left, right),
+            _ => Interpreter::exec_arithmetic(token, op, left, right),
+        }
+    }
+
+    fn exec_unary(&mut self, op: ast::UnaryOp, token: &tokenizer::Token, operand: &ast::Expr) -> Result<Value, Error> {
+        let operand = self.exec_expression(operand)?;
+        match (op, &operand) {
+            (ast::UnaryOp::Not, _) => Ok(Value::Bool(!operand.is_truthy())),
+            (ast::UnaryOp::Neg, Value::Integer(i)) => match i.checked_neg() {
+                Some(value) => Ok(Value::Integer(value)),
+                None => Err(runtime_error(token, "integer overflow")),
+            },
+            _ => Err(runtime_error(token, &format!("unsupported operand type for unary '{}': '{}'",
+                token.literal, operand.type_name()))),
+        }
+    }
+
+    fn exec_custom_op(&mut self, token: &tokenizer::Token, name: &str, left: Value, right: Value) -> Result<Value, Error> {
+        let function = match self.operators.get(name) {
+            Some(function) => function.clone(),
+            None => return Err(runtime_error(token, &format!("operator '{}' not defined", name))),
+        };
+        self.call_value(token, function, vec![left, right], Vec::new())
+    }
+
+    fn exec_map(&mut self, pairs: &[(ast::Expr, ast::Expr)]) -> Result<Value, Error> {
+        let mut entries = BTreeMap::<Key, Value>::new();
+        for (key_expr, value) in pairs {
+            let key = self.exec_expression(key_expr)?;
+            let key = key.to_key().map_err(|msg| runtime_error(key_expr.token(), &msg))?;
+            entries.insert(key, self.exec_expression(value)?);
+        }
+        Ok(Value::new_map(entries))
+    }
+
+    fn exec_items(&mut self, items: &[ast::Expr]) -> Result<Vec<Value>, Error> {
+        let mut values = Vec::<Value>::new();
+        for item in items {
+            values.push(self.exec_expression(item)?);
+        }
+        Ok(values)
+    }
+
+    fn list_position(token: &tokenizer::Token, len: usize, index: &Value) -> Result<usize, Error> {
+        match index {
+            Value::Integer(i) if *i >= 0 && (*i as usize) < len => Ok(*i as usize),
+            Value::Integer(i) => Err(runtime_error(token, &format!("list index {} out of range", i))),
+            other => Err(runtime_error(token, &format!("list index must be int, found '{}'", other.type_name()))),
+        }
+    }
+
+    /// `a.b` is a shorthand for `a["b"]` on maps.
+    fn member_key(&mut self, token: &tokenizer::Token, target: &ast::Expr, name: &tokenizer::Token) -> Result<(Value, Value), Error> {
+        let container = self.exec_expression(target)?;
+        if let Value::Map(_) = container {
+            return Ok((container, Value::Str(name.literal.clone())));
+        }
+        Err(runtime_error(token, &format!("'{}' has no member '{}'",
+            container.type_name(), name.literal)))
+    }
+
+    fn get_item(token: &tokenizer::Token, container: &Value, index: &Value) -> Result<Value, Error> {
+        match container {
+            Value::Map(map) => {
+                let key = index.to_key().map_err(|msg| runtime_error(token, &msg))?;
+                match map.borrow().get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(runtime_error(token, &format!("key {} not found", index.repr()))),
+                }
+            },
+            Value::List(list) => {
+                let list = list.borrow();
+                let position = Interpreter::list_position(token, list.len(), index)?;
+                Ok(list[position].clone())
+            },
+            Value::Tuple(items) => {
+                let position = Interpreter::list_position(token, items.len(), index)?;
+                Ok(items[position].clone())
+            },
+            Value::Str(s) => {
+                let chars: Vec<char> = s.chars().collect();
+                let position = Interpreter::list_position(token, chars.len(), index)?;
+                Ok(Value::Str(chars[position].to_string()))
+            },
+            other => Err(runtime_error(token, &format!("'{}' is not indexable", other.type_name()))),
+        }
+    }
+
+    fn exec_expression(&mut self, expr: &ast::Expr) -> Result<Value, Error> {
+        match expr {
+            ast::Expr::Integer { value, .. } => Ok(Value::Integer(*value)),
+            ast::Expr::Str { token } => Ok(Value::Str(token.literal.clone())),
+            ast::Expr::Name { token } => self.lookup_variable(token),
+            ast::Expr::List { items, .. } => Ok(Value::new_list(self.exec_items(items)?)),
+            ast::Expr::Tuple { items, .. } => Ok(Value::new_tuple(self.exec_items(items)?)),
+            ast::Expr::Map { pairs, .. } => self.exec_map(pairs),
+            ast::Expr::Index { token, target, index } => {
+                let container = self.exec_expression(target)?;
+                let index = self.exec_expression(index)?;
+                Interpreter::get_item(token, &container, &index)
+            },
+            ast::Expr::Member { token, target, name } => {
+                let (container, key) = self.member_key(token, target, name)?;
+                Interpreter::get_item(token, &container, &key)
+            },
+            ast::Expr::Call { token, callee, args } => self.exec_func_call(token, callee, args),
+            ast::Expr::Lambda { function, .. } => Ok(Value::new_function("anonymous", function, &self.env)),
+            ast::Expr::Unary { op, token, operand } => self.exec_unary(*op, token, operand),
+            ast::Expr::Binary { op, token, lhs, rhs } => self.exec_binary(op, token, lhs, rhs),
+        }
+    }
+
+    fn set_item(token: &tokenizer::Token, container: &Value, index: &Value, value: Value) -> Result<(), Error> {
+        match container {
+            Value::Map(map) => {
+                let key = index.to_key().map_err(|msg| runtime_error(token, &msg))?;
+                map.borrow_mut().insert(key, value);
+                Ok(())
+            },
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let position = Interpreter::list_position(token, list.len(), index)?;
+                list[position] = value;
+                Ok(())
+            },
+            other => Err(runtime_error(token, &format!("'{}' does not support item assignment", other.type_name()))),
+        }
+    }
+
+    fn exec_destructure(&mut self, token: &tokenizer::Token, targets: &[ast::Target], value: Value) -> Result<(), Error> {
+        let items = match &value {
+            Value::Tuple(items) => items.to_vec(),
+            Value::List(list) => list.borrow().clone(),
+            other => return Err(runtime_error(token, &format!("cannot unpack '{}'", other.type_name()))),
+        };
+        if items.len() != targets.len() {
+            return Err(runtime_error(token, &format!("cannot unpack {} values into {} targets",
+                items.len(), targets.len())));
+        }
+        for (target, item) in targets.iter().zip(items) {
+            self.assign_to(target, item)?;
+        }
+        Ok(())
+    }
+
+    fn assign_to(&mut self, target: &ast::Target, value: Value) -> Result<(), Error> {
+        match target {
+            ast::Target::Name { token } => {
+                self.set_variable(&token.literal, value);
+                Ok(())
+            },
+            ast::Target::Index { token, target, index } => {
+                let container = self.exec_expression(target)?;
+                let index = self.exec_expression(index)?;
+                Interpreter::set_item(token, &container, &index, value)
+            },
+            ast::Target::Member { token, target, name } => {
+                let (container, key) = self.member_key(token, target, name)?;
+                Interpreter::set_item(token, &container, &key, value)
+            },
+            ast::Target::Tuple { token, items } => self.exec_destructure(token, items, value),
+        }
+    }
+
+    fn exec_block(&mut self, block: &[ast::Stmt]) -> Result<Flow, Error> {
+        for statement in block {
+            match self.exec_statement(statement)? {
+                Flow::Normal => (),
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Runs one iteration of a loop body. `Ok(None)` means keep looping,
+    /// `Ok(Some(flow))` means leave the loop and continue with `flow`.
+    fn exec_loop_body(&mut self, body: &[ast::Stmt], label: &Option<tokenizer::Token>) -> Result<Option<Flow>, Error> {
+        let label = label.as_ref().map(|label| &label.literal);
+        match self.exec_block(body)? {
+            Flow::Normal => Ok(None),
+            Flow::Break(target) if target.is_none() || target.as_ref() == label => Ok(Some(Flow::Normal)),
+            Flow::Continue(target) if target.is_none() || target.as_ref() == label => Ok(None),
+            flow => Ok(Some(flow)),
+        }
+    }
+
+    fn exec_while(&mut self, label: &Option<tokenizer::Token>, condition: &ast::Expr, body: &[ast::Stmt]) -> Result<Flow, Error> {
+        while self.exec_expression(condition)?.is_truthy() {
+            if let Some(flow) = self.exec_loop_body(body, label)? {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec_for(&mut self, label: &Option<tokenizer::Token>, target: &ast::Target, iterable: &ast::Expr, body: &[ast::Stmt]) -> Result<Flow, Error> {
+        let iter = self.exec_expression(iterable)?.iter().map_err(|msg| runtime_error(iterable.token(), &msg))?;
+        for item in iter {
+            self.assign_to(target, item)?;
+            if let Some(flow) = self.exec_loop_body(body, label)? {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec_statement(&mut self, statement: &ast::Stmt) -> Result<Flow, Error> {
+        match statement {
+            ast::Stmt::Print { value, .. } => {
+                println!("{}", self.exec_expression(value)?);
+                Ok(Flow::Normal)
+            },
+            ast::Stmt::Assign { target, value, .. } => {
+                let value = self.exec_expression(value)?;
+                self.assign_to(target, value)?;
+                Ok(Flow::Normal)
+            },
+            ast::Stmt::While { label, condition, body, .. } => self.exec_while(label, condition, body),
+            ast::Stmt::For { label, target, iterable, body, .. } => self.exec_for(label, target, iterable, body),
+            ast::Stmt::Break { label, .. } => Ok(Flow::Break(label.as_ref().map(|label| label.literal.clone()))),
+            ast::Stmt::Continue { label, .. } => Ok(Flow::Continue(label.as_ref().map(|label| label.literal.clone()))),
+            ast::Stmt::Return { value, .. } => match value {
+                Some(expr) => Ok(Flow::Return(self.exec_expression(expr)?)),
+                None => Ok(Flow::Return(Value::Nil)),
+            },
+            ast::Stmt::FuncDecl { name, function } => {
+                self.exec_func_decl(name, function);
+                Ok(Flow::Normal)
+            },
+            // Operators are only declared at the top level, by `declare_functions`.
+            ast::Stmt::OperatorDecl { .. } => Ok(Flow::Normal),
+            ast::Stmt::Expression { value } => {
+                self.exec_expression(value)?;
+                Ok(Flow::Normal)
+            },
+        }
+    }
+
+    /// Functions capture the scope they are declared in. A function stored in
+    /// that same scope forms a reference cycle and is never freed, which is
+    /// acceptable until there is a garbage collector.
+    fn exec_func_decl(&mut self, name: &tokenizer::Token, function: &Rc<ast::FuncDef>) {
+        let function = Value::new_function(&name.literal, function, &self.env);
+        self.env.borrow_mut().define(&name.literal, function);
+    }
+
+    /// Declaration pass: every top-level function and operator is bound
+    /// before any statement runs, so functions may be called before their
+    /// definition and may call each other.
+    fn declare_functions(&mut self, program: &[ast::Stmt]) {
+        for statement in program {
+            match statement {
+                ast::Stmt::FuncDecl { name, function } => {
+                    let function = Value::new_function(&name.literal, function, &self.globals);
+                    self.globals.borrow_mut().define(&name.literal, function);
+                },
+                ast::Stmt::OperatorDecl { op, function, .. } => {
+                    let name = format!("operator {}", op.literal);
+                    let function = Value::new_function(&name, function, &self.globals);
+                    self.operators.insert(op.literal.clone(), function);
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// `break`, `continue` and `return` are rejected by the parser at the top
+    /// level, so the flow of a top-level statement is always `Normal`.
+    pub fn execute(&mut self, program: &[ast::Stmt]) -> Result<(), Error> {
+        self.declare_functions(program);
+        for statement in program {
+            match statement {
+                ast::Stmt::FuncDecl { .. } | ast::Stmt::OperatorDecl { .. } => (),
+                ast::Stmt::Expression { value } if self.print_expressions => match self.exec_expression(value)? {
+                    Value::Nil => (),
+                    value => println!("{}", value.repr()),
+                },
+                _ => { self.exec_statement(statement)?; },
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates an expression in the global scope.
+    pub fn evaluate(&mut self, expr: &ast::Expr) -> Result<Value, Error> {
+        self.exec_expression(expr)
+    }
+
+    pub fn globals(&self) -> Vec<ntable::Variable> {
+        self.globals.borrow().variables()
+    }
+
+    /// User defined operators and the functions implementing them, sorted.
+    pub fn operators(&self) -> Vec<(String, Value)> {
+        let mut operators: Vec<(String, Value)> = self.operators.iter()
+            .map(|(op, function)| (op.clone(), function.clone()))
+            .collect();
+        operators.sort_by(|a, b| a.0.cmp(&b.0));
+        operators
+    }
+
+    pub fn new() -> Interpreter {
+        let globals = ntable::Environment::new_global();
+        Interpreter {
+            env: globals.clone(),
+            globals,
+            operators: HashMap::new(),
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            print_expressions: false,
+        }
+    }
+
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    pub fn set_print_expressions(&mut self, print_expressions: bool) {
+        self.print_expressions = print_expressions;
+    }
+}
@@ -0,0 +1,480 @@
+//! Lexer generator. Compiles token patterns to a minimized DFA that finds
+//! the longest match at a position.
+//!
+//! Patterns support literal characters, escapes (`\n`, `\t`, `\r`, `\u4e00`
+//! and `\x` for any other `x`), `.` (anything but a newline), classes
+//! `[a-z_]` and `[^"]`, grouping, `|`, `*`, `+` and `?`.
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+pub enum Pattern {
+    /// Matches exactly this text, keywords and punctuation.
+    Literal(String),
+    Regex(String),
+}
+
+/// When several tokens match the same longest text, literals win over
+/// regular expressions, so `while` is a keyword and not a name, and earlier
+/// tokens win over later ones.
+pub struct Lexer {
+    /// Sorted start points of the character classes, a character belongs
+    /// to the class of the number of boundaries not greater than it.
+    boundaries: Vec<char>,
+    /// `transitions[state * classes + class]`, `DEAD` for no transition.
+    transitions: Vec<usize>,
+    /// The token matched by each state, as an index into the patterns.
+    accept: Vec<Option<usize>>,
+}
+
+const DEAD: usize = usize::MAX;
+
+#[derive(Default)]
+struct NfaState {
+    epsilon: Vec<usize>,
+    edges: Vec<(char, char, usize)>,
+}
+
+struct Nfa {
+    states: Vec<NfaState>,
+    accept: BTreeMap<usize, usize>,
+}
+
+/// A fragment of the NFA with one entry and one exit state.
+type Fragment = (usize, usize);
+
+struct RegexParser<'a> {
+    nfa: &'a mut Nfa,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> RegexParser<'a> {
+    fn state(&mut self) -> usize {
+        self.nfa.states.push(NfaState::default());
+        self.nfa.states.len() - 1
+    }
+
+    fn ranges(&mut self, ranges: Vec<(char, char)>) -> Fragment {
+        let (start, end) = (self.state(), self.state());
+        for (lo, hi) in ranges {
+            self.nfa.states[start].edges.push((lo, hi, end));
+        }
+        (start, end)
+    }
+
+    fn error(&self, msg: &str) -> String {
+        format!("pattern \"{}\": {}", self.chars.iter().collect::<String>(), msg)
+    }
+
+    fn alternation(&mut self) -> Result<Fragment, String> {
+        let first = self.concatenation()?;
+        if self.chars.get(self.pos) != Some(&'|') {
+            return Ok(first);
+        }
+        let (start, end) = (self.state(), self.state());
+        let mut branch = first;
+        loop {
+            self.nfa.states[start].epsilon.push(branch.0);
+            self.nfa.states[branch.1].epsilon.push(end);
+            if self.chars.get(self.pos) != Some(&'|') {
+                return Ok((start, end));
+            }
+            self.pos += 1;
+            branch = self.concatenation()?;
+        }
+    }
+
+    fn concatenation(&mut self) -> Result<Fragment, String> {
+        let start = self.state();
+        let mut end = start;
+        while let Some(c) = self.chars.get(self.pos) {
+            if *c == '|' || *c == ')' {
+                break;
+            }
+            let item = self.repetition()?;
+            self.nfa.states[end].epsilon.push(item.0);
+            end = item.1;
+        }
+        Ok((start, end))
+    }
+
+    fn repetition(&mut self) -> Result<Fragment, String> {
+        let mut item = self.atom()?;
+        while let Some(op) = self.chars.get(self.pos).copied() {
+            if !"*+?".contains(op) {
+                break;
+            }
+            self.pos += 1;
+            let (start, end) = (self.state(), self.state());
+            self.nfa.states[start].epsilon.push(item.0);
+            self.nfa.states[item.1].epsilon.push(end);
+            if op != '+' {
+                self.nfa.states[start].epsilon.push(end);
+            }
+            if op != '?' {
+                self.nfa.states[item.1].epsilon.push(item.0);
+            }
+            item = (start, end);
+        }
+        Ok(item)
+    }
+
+    fn escaped(&mut self) -> Result<char, String> {
+        self.pos += 1;
+        let c = match self.chars.get(self.pos) {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('u') => {
+                let hex: String = self.chars.iter().skip(self.pos + 1).take(4).collect();
+                let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                match c {
+                    Some(c) if hex.len() == 4 => {
+                        self.pos += 4;
+                        c
+                    },
+                    _ => return Err(self.error("expect four hex digits after '\\u'")),
+                }
+            },
+            Some(c) => *c,
+            None => return Err(self.error("trailing '\\'")),
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn atom(&mut self) -> Result<Fragment, String> {
+        match self.chars.get(self.pos).copied() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.alternation()?;
+                if self.chars.get(self.pos) != Some(&')') {
+                    return Err(self.error("expect ')'"));
+                }
+                self.pos += 1;
+                Ok(inner)
+            },
+            Some('[') => {
+                let ranges = self.class()?;
+                Ok(self.ranges(ranges))
+            },
+            Some('.') => {
+                self.pos += 1;
+                Ok(self.ranges(complement(vec![('\n', '\n')])))
+            },
+            Some('\\') => {
+                let c = self.escaped()?;
+                Ok(self.ranges(vec![(c, c)]))
+            },
+            Some(c) if "*+?".contains(c) => Err(self.error(&format!("nothing to repeat before '{}'", c))),
+            Some(c) => {
+                self.pos += 1;
+                Ok(self.ranges(vec![(c, c)]))
+            },
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn class(&mut self) -> Result<Vec<(char, char)>, String> {
+        self.pos += 1;
+        let negated = self.chars.get(self.pos) == Some(&'^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        loop {
+            let lo = match self.chars.get(self.pos).copied() {
+                Some(']') => break,
+                Some('\\') => self.escaped()?,
+                Some(c) => {
+                    self.pos += 1;
+                    c
+                },
+                None => return Err(self.error("expect ']'")),
+            };
+            let is_range = self.chars.get(self.pos) == Some(&'-') &&
+                !matches!(self.chars.get(self.pos + 1), Some(']') | None);
+            if !is_range {
+                ranges.push((lo, lo));
+                continue;
+            }
+            self.pos += 1;
+            let hi = match self.chars.get(self.pos).copied() {
+                Some('\\') => self.escaped()?,
+                Some(c) => {
+                    self.pos += 1;
+                    c
+                },
+                None => return Err(self.error("expect ']'")),
+            };
+            if hi < lo {
+                return Err(self.error(&format!("invalid range {}-{}", lo, hi)));
+            }
+            ranges.push((lo, hi));
+        }
+        self.pos += 1;
+        Ok(if negated { complement(ranges) } else { ranges })
+    }
+}
+
+/// All characters not in `ranges`.
+fn complement(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.sort();
+    let mut result = Vec::new();
+    let mut next = 0u32;
+    for (lo, hi) in ranges {
+        if (lo as u32) > next {
+            push_range(&mut result, next, lo as u32 - 1);
+        }
+        next = next.max(hi as u32 + 1);
+    }
+    push_range(&mut result, next, char::MAX as u32);
+    result
+}
+
+/// Pushes `lo..=hi`, skipping the surrogates which are not characters.
+fn push_range(ranges: &mut Vec<(char, char)>, lo: u32, hi: u32) {
+    for (lo, hi) in [(lo, hi.min(0xD7FF)), (lo.max(0xE000), hi)] {
+        if let (Some(lo), Some(hi)) = (char::from_u32(lo), char::from_u32(hi)) {
+            if lo <= hi {
+                ranges.push((lo, hi));
+            }
+        }
+    }
+}
+
+impl Nfa {
+    fn closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut result = BTreeSet::new();
+        let mut stack: Vec<usize> = states.into_iter().collect();
+        while let Some(state) = stack.pop() {
+            if result.insert(state) {
+                stack.extend(&self.states[state].epsilon);
+            }
+        }
+        result
+    }
+}
+
+impl Lexer {
+    pub fn new(patterns: &[Pattern]) -> Result<Lexer, String> {
+        let mut nfa = Nfa { states: vec![NfaState::default()], accept: BTreeMap::new() };
+        for (index, pattern) in patterns.iter().enumerate() {
+            let mut parser = RegexParser { nfa: &mut nfa, chars: Vec::new(), pos: 0 };
+            let fragment = match pattern {
+                Pattern::Literal(text) => {
+                    let start = parser.state();
+                    let mut end = start;
+                    for c in text.chars() {
+                        let (next_start, next_end) = parser.ranges(vec![(c, c)]);
+                        parser.nfa.states[end].epsilon.push(next_start);
+                        end = next_end;
+                    }
+                    (start, end)
+                },
+                Pattern::Regex(regex) => {
+                    parser.chars = regex.chars().collect();
+                    let fragment = parser.alternation()?;
+                    if parser.pos != parser.chars.len() {
+                        return Err(parser.error("unbalanced ')'"));
+                    }
+                    fragment
+                },
+            };
+            nfa.states[0].epsilon.push(fragment.0);
+            nfa.accept.insert(fragment.1, index);
+        }
+        let priority = |index: usize| (matches!(patterns[index], Pattern::Regex(_)), index);
+        let mut boundaries = BTreeSet::new();
+        for state in &nfa.states {
+            for (lo, hi, _) in &state.edges {
+                boundaries.insert(*lo);
+                if let Some(next) = char::from_u32(*hi as u32 + 1) {
+                    boundaries.insert(next);
+                }
+            }
+        }
+        let boundaries: Vec<char> = boundaries.into_iter().collect();
+        let classes = boundaries.len() + 1;
+        let class_of = |c: char| boundaries.partition_point(|b| *b <= c);
+
+        // Subset construction.
+        let mut sets = vec![nfa.closure(vec![0])];
+        let mut ids = BTreeMap::new();
+        ids.insert(sets[0].clone(), 0);
+        let mut transitions = Vec::<usize>::new();
+        let mut current = 0;
+        while current < sets.len() {
+            let mut moves = vec![BTreeSet::<usize>::new(); classes];
+            for state in &sets[current] {
+                for (lo, hi, target) in &nfa.states[*state].edges {
+                    for class in moves.iter_mut().take(class_of(*hi) + 1).skip(class_of(*lo)) {
+                        class.insert(*target);
+                    }
+                }
+            }
+            for targets in moves {
+                if targets.is_empty() {
+                    transitions.push(DEAD);
+                    continue;
+                }
+                let set = nfa.closure(targets);
+                let id = match ids.get(&set) {
+                    Some(id) => *id,
+                    None => {
+                        ids.insert(set.clone(), sets.len());
+                        sets.push(set);
+                        sets.len() - 1
+                    },
+                };
+                transitions.push(id);
+            }
+            current += 1;
+        }
+        let accept = sets.iter()
+            .map(|set| set.iter().filter_map(|state| nfa.accept.get(state)).copied().min_by_key(|index| priority(*index)))
+            .collect();
+        let mut lexer = Lexer { boundaries, transitions, accept };
+        lexer.minimize();
+        Ok(lexer)
+    }
+
+    fn classes(&self) -> usize {
+        self.boundaries.len() + 1
+    }
+
+    /// Merges equivalent states by partition refinement (Moore's algorithm).
+    fn minimize(&mut self) {
+        let classes = self.classes();
+        let states = self.accept.len();
+        let mut block: Vec<usize> = {
+            let kinds: BTreeSet<Option<usize>> = self.accept.iter().copied().collect();
+            let kinds: Vec<Option<usize>> = kinds.into_iter().collect();
+            self.accept.iter().map(|accept| kinds.iter().position(|kind| kind == accept).unwrap()).collect()
+        };
+        let mut count = block.iter().max().unwrap() + 1;
+        loop {
+            let mut signatures = BTreeMap::<Vec<usize>, usize>::new();
+            let mut next = Vec::with_capacity(states);
+            for state in 0..states {
+                let mut signature = vec![block[state]];
+                signature.extend(self.transitions[state * classes..(state + 1) * classes].iter()
+                    .map(|target| if *target == DEAD { DEAD } else { block[*target] }));
+                let id = signatures.len();
+                next.push(*signatures.entry(signature).or_insert(id));
+            }
+            let done = signatures.len() == count;
+            count = signatures.len();
+            block = next;
+            if done {
+                break;
+            }
+        }
+        // The start state stays state 0.
+        let mut order = vec![DEAD; count];
+        order[block[0]] = 0;
+        let mut used = 1;
+        for b in &block {
+            if order[*b] == DEAD {
+                order[*b] = used;
+                used += 1;
+            }
+        }
+        let mut transitions = vec![DEAD; count * classes];
+        let mut accept = vec![None; count];
+        for state in 0..states {
+            let new = order[block[state]];
+            accept[new] = self.accept[state];
+            for class in 0..classes {
+                let target = self.transitions[state * classes + class];
+                transitions[new * classes + class] = if target == DEAD { DEAD } else { order[block[target]] };
+            }
+        }
+        self.transitions = transitions;
+        self.accept = accept;
+    }
+
+    /// The token and length of the longest match at the start of `chars`.
+    pub fn longest_match(&self, chars: &[char]) -> Option<(usize, usize)> {
+        let classes = self.classes();
+        let mut state = 0;
+        let mut result = None;
+        for (i, c) in chars.iter().enumerate() {
+            let class = self.boundaries.partition_point(|b| b <= c);
+            state = self.transitions[state * classes + class];
+            if state == DEAD {
+                break;
+            }
+            if let Some(token) = self.accept[state] {
+                result = Some((token, i + 1));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lexer, Pattern};
+
+    const WHILE: usize = 0;
+    const DOT_DOT: usize = 1;
+    const DOT_DOT_EQUAL: usize = 2;
+    const DOT: usize = 3;
+    const NAME: usize = 4;
+    const STRING: usize = 5;
+
+    fn lexer() -> Lexer {
+        Lexer::new(&[
+            Pattern::Literal("while".to_string()),
+            Pattern::Literal("..".to_string()),
+            Pattern::Literal("..=".to_string()),
+            Pattern::Literal(".".to_string()),
+            Pattern::Regex("[a-zA-Z_][a-zA-Z0-9_]*".to_string()),
+            Pattern::Regex("\"([^\"\\\\\\n]|\\\\.)*\"".to_string()),
+        ]).unwrap()
+    }
+
+    fn longest_match(text: &str) -> Option<(usize, usize)> {
+        lexer().longest_match(&text.chars().collect::<Vec<char>>())
+    }
+
+    fn error(pattern: &str) -> String {
+        Lexer::new(&[Pattern::Regex(pattern.to_string())]).err().unwrap()
+    }
+
+    #[test]
+    fn keywords_win_over_names_of_the_same_length() {
+        assert_eq!(longest_match("while x"), Some((WHILE, 5)));
+        assert_eq!(longest_match("whilex"), Some((NAME, 6)));
+        assert_eq!(longest_match("whil"), Some((NAME, 4)));
+        assert_eq!(longest_match("while_1("), Some((NAME, 7)));
+    }
+
+    #[test]
+    fn longest_punctuation_wins() {
+        assert_eq!(longest_match("..=5"), Some((DOT_DOT_EQUAL, 3)));
+        assert_eq!(longest_match("..5"), Some((DOT_DOT, 2)));
+        assert_eq!(longest_match(".x"), Some((DOT, 1)));
+        assert_eq!(longest_match("...="), Some((DOT_DOT, 2)));
+    }
+
+    #[test]
+    fn strings_need_their_closing_quote() {
+        assert_eq!(longest_match("\"a\\\"b\" c"), Some((STRING, 6)));
+        assert_eq!(longest_match("\"abc"), None);
+        assert_eq!(longest_match("\"ab\nc\""), None);
+        assert_eq!(longest_match("+"), None);
+    }
+
+    #[test]
+    fn invalid_patterns_are_errors() {
+        assert_eq!(error("[a-"), "pattern \"[a-\": expect ']'");
+        assert_eq!(error("[z-a]"), "pattern \"[z-a]\": invalid range z-a");
+        assert_eq!(error("(ab"), "pattern \"(ab\": expect ')'");
+        assert_eq!(error("ab)"), "pattern \"ab)\": unbalanced ')'");
+        assert_eq!(error("*a"), "pattern \"*a\": nothing to repeat before '*'");
+        assert_eq!(error("\\u12"), "pattern \"\\u12\": expect four hex digits after '\\u'");
+        assert_eq!(error("a\\"), "pattern \"a\\\": trailing '\\'");
+    }
+}
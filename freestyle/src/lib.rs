@@ -1,26 +1,139 @@
-
-use std::fs;
-mod tokenizer;
-mod parser;
-mod ast;
-mod interpreter;
-mod ntable;
-#[macro_use]
-extern crate lazy_static;
-
-pub fn run(filename: &String) -> Result<(),String> {
-    
-    let contents = fs::read_to_string(filename)
-        .expect(&format!("Read content from {} failed", filename));
-    let tokenizer = tokenizer::Tokenizer::new(&contents);
-    let mut parser = parser::Parser::new(tokenizer);
-    let tree = match parser.parse() {
-        Ok(tree) => tree,
-        Err(msg) => return Err(msg),
-    };
-    let mut intp = interpreter::Interpreter::new();
-    return match intp.execute(&tree) {
-        Ok(_) => Ok(()),
-        Err(msg) => Err(msg),
-    };
-}
+use std::fs;
+use std::io;
+use std::thread;
+mod tokenizer;
+mod parser;
+mod syntax;
+pub mod ast;
+mod interpreter;
+mod ntable;
+mod value;
+mod builtin;
+mod lexgen;
+pub mod dump;
+mod formatter;
+pub mod printer;
+mod lineedit;
+mod repl;
+pub mod error;
+pub mod grammar;
+#[macro_use]
+extern crate lazy_static;
+
+pub use interpreter::DEFAULT_MAX_CALL_DEPTH;
+pub use tokenizer::{Token, TokenType};
+
+/// Stack reserved for every level of interpreted calls. Each call goes
+/// through a dozen Rust frames, more with nested expressions.
+const STACK_PER_CALL: usize = 32 * 1024;
+const MIN_STACK_SIZE: usize = 8 * 1024 * 1024;
+/// Highest call depth accepted, its stack of 3 GiB is reserved up front.
+pub const MAX_CALL_DEPTH: usize = 100_000;
+
+/// Runs `task` on a thread whose stack is large enough for `max_call_depth`
+/// calls, recursion in the script recurses in the interpreter.
+fn with_call_stack<F>(max_call_depth: usize, task: F) -> Result<(), error::Error>
+where F: FnOnce() -> Result<(), error::Error> + Send + 'static {
+    let stack_size = max_call_depth.saturating_mul(STACK_PER_CALL).max(MIN_STACK_SIZE);
+    let worker = thread::Builder::new().stack_size(stack_size).spawn(task);
+    match worker {
+        Ok(handle) => match handle.join() {
+            Ok(result) => result,
+            Err(_) => Err(error::Error::new("interpreter thread panicked".to_string())),
+        },
+        Err(err) => Err(error::Error::new(format!("failed to start interpreter thread: {}", err))),
+    }
+}
+
+/// Reads a source file, `-` reads standard input.
+pub fn read_source(filename: &str) -> Result<String, error::Error> {
+    let result = match filename {
+        "-" => io::read_to_string(io::stdin()),
+        _ => fs::read_to_string(filename),
+    };
+    result.map_err(|err| error::Error::io(format!("read {} failed: {}", filename, err)))
+}
+
+/// Runs a program, `print_expressions` prints the values of its top-level
+/// expression statements.
+pub fn run(source: &str, max_call_depth: usize, print_expressions: bool) -> Result<(), error::Error> {
+    let source = source.to_string();
+    with_call_stack(max_call_depth, move || {
+        let tree = parse(&source)?;
+        let mut intp = interpreter::Interpreter::new();
+        intp.set_max_call_depth(max_call_depth);
+        intp.set_print_expressions(print_expressions);
+        intp.execute(&tree)
+    })
+}
+
+/// Starts an interactive session on stdin, `quiet` leaves out the banner.
+pub fn repl(max_call_depth: usize, quiet: bool) -> Result<(), error::Error> {
+    with_call_stack(max_call_depth, move || {
+        repl::Repl::new(max_call_depth).run(quiet);
+        Ok(())
+    })
+}
+
+/// Parses source code into its AST, e.g. for passes built on `ast::Visitor`.
+pub fn parse(source: &str) -> Result<Vec<ast::Stmt>, error::Error> {
+    let tokenizer = tokenizer::Tokenizer::new(source);
+    let mut parser = parser::Parser::new(tokenizer);
+    Ok(parser.parse()?)
+}
+
+/// Splits source code into tokens, the last one is `TokenType::Eof`.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, error::Error> {
+    Ok(tokenizer::Tokenizer::new(source).tokens()?)
+}
+
+/// Lists the tokens of source code, one per line with their position, type
+/// and literal.
+pub fn dump_tokens(source: &str) -> Result<String, error::Error> {
+    let mut dump = String::new();
+    for token in tokenize(source)? {
+        dump.push_str(&format!("{}:{}\t{:?}\t{:?}\n", token.row, token.col, token.token_type, token.literal));
+    }
+    Ok(dump)
+}
+
+/// Parses source code and dumps its AST, as JSON or as an S-expression.
+pub fn dump_ast(source: &str, json: bool) -> Result<String, error::Error> {
+    let tree = parse(source)?;
+    match json {
+        true => Ok(dump::json(&tree)),
+        false => Ok(dump::sexpr(&tree)),
+    }
+}
+
+/// Formats source code as `freestyle fmt` does.
+pub fn format(source: &str) -> Result<String, error::Error> {
+    Ok(formatter::format(source)?)
+}
+
+/// Formats a source file in place. With `check` the file is left untouched.
+/// Returns whether the file was already formatted.
+pub fn format_file(filename: &str, check: bool) -> Result<bool, error::Error> {
+    let contents = read_source(filename)?;
+    let formatted = format(&contents)?;
+    if formatted == contents {
+        return Ok(true);
+    }
+    if !check {
+        fs::write(filename, formatted)
+            .map_err(|err| error::Error::io(format!("write {} failed: {}", filename, err)))?;
+    }
+    Ok(false)
+}
+
+/// Reads a grammar file and generates a parser for it, see `grammar`.
+/// Returns every problem found when the grammar is not LL(1).
+pub fn gen_parser(filename: &str) -> Result<String, Vec<String>> {
+    let contents = read_source(filename).map_err(|err| vec![err.message])?;
+    let grammar = grammar::Grammar::parse(&contents).map_err(|msg| vec![msg])?;
+    let table = grammar.analyze();
+    if !table.conflicts.is_empty() {
+        return Err(table.conflicts);
+    }
+    Ok(grammar.generate(&table))
+}
@@ -0,0 +1,352 @@
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const MAX_HISTORY: usize = 1000;
+
+/// Reads lines from the terminal with cursor movement, history and tab
+/// completion. The terminal is switched to non-canonical mode with `stty`
+/// only while a line is edited, so Ctrl-C interrupts the code that runs in
+/// between. When stdin is not a terminal, lines are read as is.
+pub struct LineEditor {
+    /// The settings of the terminal, `None` when stdin is not a terminal.
+    saved_mode: Option<String>,
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+}
+
+/// What `LineEditor::read_line` read.
+pub enum Input {
+    /// A line without its line break.
+    Line(String),
+    /// Ctrl-C was pressed, the pending input is discarded.
+    Interrupted,
+    End,
+}
+
+/// The terminal in non-canonical mode without echo and signals, the
+/// settings it had before are restored when this is dropped.
+struct RawMode<'a> {
+    saved_mode: &'a str,
+}
+
+impl<'a> RawMode<'a> {
+    fn enter(saved_mode: &'a str) -> Option<RawMode<'a>> {
+        stty(&["-icanon", "-echo", "-isig", "-ixon", "min", "1", "time", "0"])?;
+        Some(RawMode { saved_mode })
+    }
+}
+
+impl Drop for RawMode<'_> {
+    fn drop(&mut self) {
+        stty(&[self.saved_mode]);
+    }
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Tab,
+    KillStart,
+    KillEnd,
+    Interrupt,
+    EndOfInput,
+    Ignored,
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).stderr(Stdio::null()).output().ok()?;
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => None,
+    }
+}
+
+fn read_byte() -> Option<u8> {
+    let mut byte = [0u8];
+    match io::stdin().read(&mut byte) {
+        Ok(1) => Some(byte[0]),
+        _ => None,
+    }
+}
+
+/// Decodes the rest of a UTF-8 sequence starting with `first`.
+fn read_char(first: u8) -> Option<char> {
+    let len = match first {
+        0xf0..=0xf7 => 4,
+        0xe0..=0xef => 3,
+        0xc0..=0xdf => 2,
+        _ => 1,
+    };
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        bytes.push(read_byte()?);
+    }
+    std::str::from_utf8(&bytes).ok()?.chars().next()
+}
+
+fn read_key() -> Option<Key> {
+    let key = match read_byte()? {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        b'\t' => Key::Tab,
+        0x01 => Key::Home,
+        0x05 => Key::End,
+        0x02 => Key::Left,
+        0x06 => Key::Right,
+        0x10 => Key::Up,
+        0x0e => Key::Down,
+        0x15 => Key::KillStart,
+        0x0b => Key::KillEnd,
+        0x03 => Key::Interrupt,
+        0x04 => Key::EndOfInput,
+        0x1b => match (read_byte()?, read_byte()?) {
+            (b'[', b'A') | (b'O', b'A') => Key::Up,
+            (b'[', b'B') | (b'O', b'B') => Key::Down,
+            (b'[', b'C') | (b'O', b'C') => Key::Right,
+            (b'[', b'D') | (b'O', b'D') => Key::Left,
+            (b'[', b'H') | (b'O', b'H') => Key::Home,
+            (b'[', b'F') | (b'O', b'F') => Key::End,
+            (b'[', digit @ b'1'..=b'8') => match (digit, read_byte()?) {
+                (b'3', b'~') => Key::Delete,
+                (b'1', b'~') | (b'7', b'~') => Key::Home,
+                (b'4', b'~') | (b'8', b'~') => Key::End,
+                _ => Key::Ignored,
+            },
+            _ => Key::Ignored,
+        },
+        byte if byte < 0x20 => Key::Ignored,
+        byte => Key::Char(read_char(byte)?),
+    };
+    Some(key)
+}
+
+/// Columns a character takes on the terminal, CJK characters take two.
+fn width(c: char) -> usize {
+    match c {
+        '\u{1100}'..='\u{115f}' | '\u{2e80}'..='\u{a4cf}' | '\u{ac00}'..='\u{d7a3}' |
+        '\u{f900}'..='\u{faff}' | '\u{fe30}'..='\u{fe4f}' | '\u{ff00}'..='\u{ff60}' |
+        '\u{ffe0}'..='\u{ffe6}' => 2,
+        _ => 1,
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == ':'
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let mut prefix: Vec<char> = words[0].chars().collect();
+    for word in &words[1..] {
+        let len = prefix.iter().zip(word.chars()).take_while(|(a, b)| **a == *b).count();
+        prefix.truncate(len);
+    }
+    prefix.into_iter().collect()
+}
+
+/// The line being edited.
+struct Buffer {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl Buffer {
+    fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    fn insert(&mut self, text: &str) {
+        for c in text.chars() {
+            self.chars.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    fn redraw(&self, prompt: &str) {
+        let behind: usize = self.chars[self.cursor..].iter().map(|c| width(*c)).sum();
+        let mut out = format!("\r{}{}\x1b[K", prompt, self.text());
+        if behind > 0 {
+            out.push_str(&format!("\x1b[{}D", behind));
+        }
+        print!("{}", out);
+        io::stdout().flush().ok();
+    }
+}
+
+impl LineEditor {
+    /// History is kept in `~/.freestyle_history`.
+    pub fn new() -> LineEditor {
+        let history_file = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".freestyle_history"));
+        let history = match &history_file {
+            Some(path) => fs::read_to_string(path).unwrap_or_default().lines().map(|line| line.to_string()).collect(),
+            None => Vec::new(),
+        };
+        let mut editor = LineEditor {
+            saved_mode: stty(&["-g"]),
+            history,
+            history_file,
+        };
+        let excess = editor.history.len().saturating_sub(MAX_HISTORY);
+        editor.history.drain(..excess);
+        editor
+    }
+
+    /// The history file is rewritten with the last `MAX_HISTORY` lines.
+    fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(|last| last.as_str()) == Some(line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        let excess = self.history.len().saturating_sub(MAX_HISTORY);
+        self.history.drain(..excess);
+        if let Some(path) = &self.history_file {
+            let mut contents = self.history.join("\n");
+            contents.push('\n');
+            fs::write(path, contents).ok();
+        }
+    }
+
+    /// Completes the word before the cursor. A unique candidate is inserted,
+    /// otherwise their common prefix is, or all candidates are listed.
+    fn complete(&self, buffer: &mut Buffer, prompt: &str, candidates: &dyn Fn(&str) -> Vec<String>) {
+        let start = buffer.chars[..buffer.cursor].iter().rposition(|c| !is_word(*c)).map_or(0, |i| i + 1);
+        let word: String = buffer.chars[start..buffer.cursor].iter().collect();
+        let mut matches: Vec<String> = candidates(&word).into_iter().filter(|c| c.starts_with(&word)).collect();
+        matches.sort();
+        matches.dedup();
+        if matches.is_empty() {
+            return;
+        }
+        let prefix = common_prefix(&matches);
+        if matches.len() == 1 {
+            buffer.insert(&format!("{} ", &prefix[word.len()..]));
+        } else if prefix.len() > word.len() {
+            buffer.insert(&prefix[word.len()..]);
+        } else {
+            println!("\r\n{}", matches.join("  "));
+        }
+        buffer.redraw(prompt);
+    }
+
+    fn edit(&mut self, prompt: &str, candidates: &dyn Fn(&str) -> Vec<String>) -> Input {
+        let mut buffer = Buffer { chars: Vec::new(), cursor: 0 };
+        // The line being typed is kept when browsing the history.
+        let mut history_index = self.history.len();
+        let mut typed = String::new();
+        buffer.redraw(prompt);
+        loop {
+            let key = match read_key() {
+                Some(key) => key,
+                None => return Input::End,
+            };
+            match key {
+                Key::Enter => {
+                    println!();
+                    return Input::Line(buffer.text());
+                },
+                Key::Char(c) => buffer.insert(&c.to_string()),
+                Key::Backspace if buffer.cursor > 0 => {
+                    buffer.cursor -= 1;
+                    buffer.chars.remove(buffer.cursor);
+                },
+                Key::Delete if buffer.cursor < buffer.chars.len() => {
+                    buffer.chars.remove(buffer.cursor);
+                },
+                Key::Left if buffer.cursor > 0 => buffer.cursor -= 1,
+                Key::Right if buffer.cursor < buffer.chars.len() => buffer.cursor += 1,
+                Key::Home => buffer.cursor = 0,
+                Key::End => buffer.cursor = buffer.chars.len(),
+                Key::Up if history_index > 0 => {
+                    if history_index == self.history.len() {
+                        typed = buffer.text();
+                    }
+                    history_index -= 1;
+                    buffer.set(&self.history[history_index]);
+                },
+                Key::Down if history_index < self.history.len() => {
+                    history_index += 1;
+                    match self.history.get(history_index) {
+                        Some(line) => buffer.set(line),
+                        None => buffer.set(&typed),
+                    }
+                },
+                Key::KillStart => {
+                    buffer.chars.drain(..buffer.cursor);
+                    buffer.cursor = 0;
+                },
+                Key::KillEnd => buffer.chars.truncate(buffer.cursor),
+                Key::Tab => self.complete(&mut buffer, prompt, candidates),
+                Key::Interrupt => {
+                    println!("^C");
+                    return Input::Interrupted;
+                },
+                Key::EndOfInput if buffer.chars.is_empty() => return Input::End,
+                Key::EndOfInput if buffer.cursor < buffer.chars.len() => {
+                    buffer.chars.remove(buffer.cursor);
+                },
+                _ => (),
+            }
+            buffer.redraw(prompt);
+        }
+    }
+
+    /// Reads a line, `candidates` lists the completions of a word. The
+    /// terminal is back in its own mode when this returns.
+    pub fn read_line(&mut self, prompt: &str, candidates: &dyn Fn(&str) -> Vec<String>) -> Input {
+        let saved_mode = self.saved_mode.clone();
+        let raw_mode = saved_mode.as_deref().and_then(RawMode::enter);
+        if raw_mode.is_none() {
+            print!("{}", prompt);
+            io::stdout().flush().ok();
+            let mut line = String::new();
+            return match io::stdin().lock().read_line(&mut line) {
+                Ok(0) | Err(_) => Input::End,
+                Ok(_) => Input::Line(line.trim_end_matches(&['\n', '\r'][..]).to_string()),
+            };
+        }
+        let input = self.edit(prompt, candidates);
+        drop(raw_mode);
+        if let Input::Line(line) = &input {
+            self.add_history(line);
+        }
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineEditor, MAX_HISTORY};
+    use std::fs;
+
+    #[test]
+    fn history_file_keeps_the_last_lines() {
+        let path = std::env::temp_dir().join(format!("freestyle-history-{}", std::process::id()));
+        let mut editor = LineEditor { saved_mode: None, history: Vec::new(), history_file: Some(path.clone()) };
+        for i in 0..MAX_HISTORY + 5 {
+            editor.add_history(&format!("print {}", i));
+            editor.add_history(&format!("print {}", i));
+            editor.add_history("  ");
+        }
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), MAX_HISTORY);
+        assert_eq!(lines[0], "print 5");
+        assert_eq!(lines[MAX_HISTORY - 1], format!("print {}", MAX_HISTORY + 4));
+        assert_eq!(editor.history, lines);
+    }
+}
@@ -1,19 +1,280 @@
 use std::env;
+use std::process;
+use freestyle::error::{Error, ErrorKind};
 
-fn usage() {
-    println!("Usage: freestyle <source_file>");
+const USAGE: &str = "\
+Usage: freestyle [<command>] [<options>] [<file> | - | -e <code>]
+
+Commands:
+  run        run a program, the default when a program is given
+  check      check programs for syntax errors
+  fmt        format source files in place
+  repl       start an interactive session, the default without a program
+  tokens     list the tokens of a program
+  ast        dump the syntax tree of a program
+  compile    generate a parser from a grammar file
+
+Options:
+  -e <code>               the program is <code> instead of a file
+  -                       read the program from standard input
+  -q, --quiet             print nothing but the program's output and errors
+  --max-call-depth <n>    run, repl: limit of nested calls, 1000 by default,
+                          at most 100000
+  --print-expressions     run: print the values of top-level expression statements
+  --check                 fmt: only report files that are not formatted
+  --json                  ast: dump JSON instead of an S-expression
+  -h, --help              show this help
+
+Exit status: 0 on success, 1 on a runtime or I/O error, 2 on a syntax error.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Run,
+    Check,
+    Fmt,
+    Repl,
+    Tokens,
+    Ast,
+    Compile,
+}
+
+impl Command {
+    fn parse(name: &str) -> Option<Command> {
+        let command = match name {
+            "run" => Command::Run,
+            "check" => Command::Check,
+            "fmt" => Command::Fmt,
+            "repl" => Command::Repl,
+            "tokens" => Command::Tokens,
+            "ast" => Command::Ast,
+            "compile" => Command::Compile,
+            _ => return None,
+        };
+        Some(command)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Command::Run => "run",
+            Command::Check => "check",
+            Command::Fmt => "fmt",
+            Command::Repl => "repl",
+            Command::Tokens => "tokens",
+            Command::Ast => "ast",
+            Command::Compile => "compile",
+        }
+    }
+
+    /// Options the command accepts besides `--quiet`.
+    fn options(self) -> &'static [&'static str] {
+        match self {
+            Command::Run => &["-e", "--max-call-depth", "--print-expressions"],
+            Command::Check | Command::Tokens => &["-e"],
+            Command::Fmt => &["--check"],
+            Command::Repl => &["--max-call-depth"],
+            Command::Ast => &["-e", "--json"],
+            Command::Compile => &[],
+        }
+    }
+}
+
+struct Options {
+    command: Command,
+    quiet: bool,
+    /// Program given with `-e`.
+    code: Option<String>,
+    max_call_depth: usize,
+    print_expressions: bool,
+    check: bool,
+    json: bool,
+    /// Files, `-` is standard input.
+    files: Vec<String>,
+}
+
+/// The command is the first argument, only `--quiet` may come before it.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let position = args.iter().position(|arg| arg != "-q" && arg != "--quiet").unwrap_or(args.len());
+    let command = args.get(position).and_then(|arg| Command::parse(arg));
+    let mut options = Options {
+        command: command.unwrap_or(Command::Run),
+        quiet: false,
+        code: None,
+        max_call_depth: freestyle::DEFAULT_MAX_CALL_DEPTH,
+        print_expressions: false,
+        check: false,
+        json: false,
+        files: Vec::new(),
+    };
+    let mut flags = Vec::new();
+    let mut rest = args[..position].iter().chain(args[position + command.map_or(0, |_| 1)..].iter());
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-q" | "--quiet" => options.quiet = true,
+            "-e" => match rest.next() {
+                Some(code) if options.code.is_none() => options.code = Some(code.clone()),
+                Some(_) => return Err("-e given more than once".to_string()),
+                None => return Err("-e needs code".to_string()),
+            },
+            "--max-call-depth" => match rest.next().map(|depth| depth.parse::<usize>()) {
+                Some(Ok(depth)) if depth > 0 && depth <= freestyle::MAX_CALL_DEPTH => options.max_call_depth = depth,
+                _ => return Err(format!("--max-call-depth needs a number from 1 to {}", freestyle::MAX_CALL_DEPTH)),
+            },
+            "--print-expressions" => options.print_expressions = true,
+            "--check" => options.check = true,
+            "--json" => options.json = true,
+            "-" => options.files.push(arg.clone()),
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            _ => options.files.push(arg.clone()),
+        }
+        if arg.starts_with('-') && arg != "-" && arg != "-q" && arg != "--quiet" {
+            flags.push(arg.as_str());
+        }
+    }
+    if command.is_none() && options.code.is_none() && options.files.is_empty() {
+        options.command = Command::Repl;
+    }
+    let command = options.command;
+    if let Some(flag) = flags.iter().find(|flag| !command.options().contains(flag)) {
+        return Err(format!("{} does not take {}", command.name(), flag));
+    }
+    let programs = options.files.len() + options.code.iter().count();
+    let valid = match command {
+        Command::Run | Command::Tokens | Command::Ast => programs == 1,
+        Command::Check | Command::Fmt => programs > 0,
+        Command::Repl => programs == 0,
+        Command::Compile => options.files.len() == 1,
+    };
+    match valid {
+        true => Ok(options),
+        false => Err(format!("wrong number of files for {}", command.name())),
+    }
+}
+
+fn exit_code(err: &Error) -> i32 {
+    match err.kind {
+        ErrorKind::Syntax => 2,
+        ErrorKind::Runtime | ErrorKind::Io => 1,
+    }
+}
+
+fn report(result: Result<(), Error>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit_code(&err)
+        },
+    }
+}
+
+/// The single program of `run`, `tokens` and `ast`.
+fn program(options: &Options) -> Result<String, Error> {
+    match &options.code {
+        Some(code) => Ok(code.clone()),
+        None => freestyle::read_source(&options.files[0]),
+    }
+}
+
+fn print_dump(options: &Options, dump: fn(&str) -> Result<String, Error>) -> i32 {
+    report(program(options).and_then(|source| dump(&source)).map(|dump| print!("{}", dump)))
+}
+
+fn check(options: &Options) -> i32 {
+    let mut programs: Vec<(&str, Result<String, Error>)> = Vec::new();
+    if let Some(code) = &options.code {
+        programs.push(("-e", Ok(code.clone())));
+    }
+    for file in &options.files {
+        programs.push((file, freestyle::read_source(file)));
+    }
+    let mut status = 0;
+    for (name, source) in programs {
+        match source.and_then(|source| freestyle::parse(&source)) {
+            Ok(_) if !options.quiet => println!("{}: ok", name),
+            Ok(_) => (),
+            Err(err) => {
+                eprintln!("{}: {}", name, err);
+                status = status.max(exit_code(&err));
+            },
+        }
+    }
+    status
+}
+
+/// `-` formats standard input to standard output.
+fn fmt_stdin(options: &Options) -> Result<bool, Error> {
+    let source = freestyle::read_source("-")?;
+    let formatted = freestyle::format(&source)?;
+    if !options.check {
+        print!("{}", formatted);
+    }
+    Ok(formatted == source)
+}
+
+fn fmt(options: &Options) -> i32 {
+    let mut status = 0;
+    for file in &options.files {
+        let result = match file.as_str() {
+            "-" => fmt_stdin(options),
+            _ => freestyle::format_file(file, options.check),
+        };
+        match result {
+            Ok(true) => (),
+            Ok(false) if options.check => {
+                if !options.quiet {
+                    println!("{} is not formatted", file);
+                }
+                status = status.max(1);
+            },
+            Ok(false) if options.quiet || file == "-" => (),
+            Ok(false) => println!("formatted {}", file),
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                status = status.max(exit_code(&err));
+            },
+        }
+    }
+    status
+}
+
+fn compile(options: &Options) -> i32 {
+    match freestyle::gen_parser(&options.files[0]) {
+        Ok(code) => {
+            print!("{}", code);
+            0
+        },
+        Err(problems) => {
+            for problem in problems {
+                eprintln!("{}", problem);
+            }
+            1
+        },
+    }
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        usage();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") || args.first().map(|arg| arg.as_str()) == Some("help") {
+        println!("{}", USAGE);
         return;
     }
-    let filename: &String = &args[1];
-    println!("Executing {}...", filename);
-    match freestyle::run(filename) {
-        Ok(_) => println!("ok"),
-        Err(msg) => println!("{}", msg),
-    }
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}\nrun freestyle --help for usage", msg);
+            process::exit(2);
+        },
+    };
+    let status = match options.command {
+        Command::Run => report(program(&options)
+            .and_then(|source| freestyle::run(&source, options.max_call_depth, options.print_expressions))),
+        Command::Check => check(&options),
+        Command::Fmt => fmt(&options),
+        Command::Repl => report(freestyle::repl(options.max_call_depth, options.quiet)),
+        Command::Tokens => print_dump(&options, freestyle::dump_tokens),
+        Command::Ast if options.json => print_dump(&options, |source| freestyle::dump_ast(source, true)),
+        Command::Ast => print_dump(&options, |source| freestyle::dump_ast(source, false)),
+        Command::Compile => compile(&options),
+    };
+    process::exit(status);
 }
@@ -1,46 +1,113 @@
-use std::collections::HashMap;
-
-pub struct Variable {
-    pub name: String,
-    pub value: i64,
-}
-
-pub struct NameTable {
-    map: HashMap<String, Variable>,
-}
-
-impl Variable {
-    pub fn new(string: &String, value: i64) -> Variable {
-        Variable {
-            name: string.clone(),
-            value: value,
-        }
-    }
-}
-
-impl Clone for Variable {
-    fn clone(&self) -> Self {
-        Variable {
-            name: self.name.clone(),
-            value: self.value,
-        }
-    }
-}
-
-impl NameTable {
-    pub fn get(&self, key: &String) -> Result<Variable, String> {
-        let value = self.map.get(key);
-        match value {
-            Some(v) => return Ok(v.clone()),
-            None => return Err(format!("variable '{}' not found!", key)),
-        }
-    }
-    pub fn set(&mut self, key: String, variable: Variable) {
-        self.map.insert(key, variable);
-    }
-    pub fn new() -> NameTable {
-        NameTable {
-            map: HashMap::<String, Variable>::new(),
-        }
-    }
-}
\ No newline at end of file
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use super::value::Value;
+
+#[derive(Clone)]
+pub struct Variable {
+    pub name: String,
+    pub value: Value,
+}
+
+pub struct NameTable {
+    map: HashMap<String, Variable>,
+}
+
+impl Variable {
+    pub fn new(string: &str, value: Value) -> Variable {
+        Variable {
+            name: string.to_string(),
+            value,
+        }
+    }
+}
+
+impl NameTable {
+    pub fn get(&self, key: &str) -> Result<Variable, String> {
+        match self.map.get(key) {
+            Some(v) => Ok(v.clone()),
+            None => Err(format!("variable '{}' not found!", key)),
+        }
+    }
+    pub fn contains(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+    /// All variables, sorted by name.
+    pub fn variables(&self) -> Vec<Variable> {
+        let mut variables: Vec<Variable> = self.map.values().cloned().collect();
+        variables.sort_by(|a, b| a.name.cmp(&b.name));
+        variables
+    }
+    pub fn set(&mut self, variable: Variable) {
+        self.map.insert(variable.name.clone(), variable);
+    }
+    pub fn new() -> NameTable {
+        NameTable {
+            map: HashMap::<String, Variable>::new(),
+        }
+    }
+}
+
+pub type Env = Rc<RefCell<Environment>>;
+
+/// A scope of variables. The global scope has no parent, every call of a
+/// function gets a new scope whose parent is the scope the function was
+/// created in, which is how closures see the variables around them.
+pub struct Environment {
+    table: NameTable,
+    parent: Option<Env>,
+}
+
+impl Environment {
+    pub fn new_global() -> Env {
+        Rc::new(RefCell::new(Environment {
+            table: NameTable::new(),
+            parent: None,
+        }))
+    }
+
+    pub fn new_child(parent: &Env) -> Env {
+        Rc::new(RefCell::new(Environment {
+            table: NameTable::new(),
+            parent: Some(parent.clone()),
+        }))
+    }
+
+    pub fn lookup(&self, name: &str) -> Result<Value, String> {
+        match self.table.get(name) {
+            Ok(variable) => Ok(variable.value),
+            Err(msg) => match &self.parent {
+                Some(parent) => parent.borrow().lookup(name),
+                None => Err(msg),
+            },
+        }
+    }
+
+    /// Variables of this scope only, sorted by name.
+    pub fn variables(&self) -> Vec<Variable> {
+        self.table.variables()
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.table.set(Variable::new(name, value));
+    }
+
+    /// Updates the nearest binding of `name` in an enclosing function scope,
+    /// so closures can modify captured variables. Globals are never updated
+    /// from inside a function, the assignment creates a local instead.
+    pub fn assign(env: &Env, name: &str, value: Value) {
+        let mut current = env.clone();
+        loop {
+            let parent = match &current.borrow().parent {
+                Some(parent) => parent.clone(),
+                None => break,
+            };
+            if current.borrow().table.contains(name) {
+                current.borrow_mut().define(name, value);
+                return;
+            }
+            current = parent;
+        }
+        env.borrow_mut().define(name, value);
+    }
+}
@@ -1,331 +1,683 @@
-use super::tokenizer::*;
-use super::ast;
-
-pub struct Parser {
-    tokenizer: Tokenizer
-}
-
-impl Parser {
-    fn function_call(&mut self, func: Token) -> Result<ast::AstNode, String> {
-        self.tokenizer.eat(1);
-        let mut func_node = ast::AstNode::new(ast::NodeType::FuncCall, func);
-        loop {
-            let arg = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            self.tokenizer.eat(1);
-            match arg.token_type {
-                TokenType::Symbol => (),
-                _ => return Err(format!("line:{}, column:{}, syntax error, expect argument, found '{}'",
-                    arg.row, arg.col, arg.literal)),
-            }
-            func_node.add_node(ast::AstNode::new(ast::NodeType::Arg, arg));
-            let next_token = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            match next_token.token_type {
-                TokenType::Comma => (),
-                TokenType::RP => break,
-                _ => return Err(format!("line:{}, column:{}, syntax error, expect ',' or ')'), found '{}'",
-                    next_token.row, next_token.col, next_token.literal)),
-            }
-        }
-        Ok(func_node)
-    }
-    fn expression_integer_or_name(&mut self) -> Result<ast::AstNode, String> {
-        let token = match self.tokenizer.look_ahead(1) {
-            Ok(token) => token,
-            Err(msg) => return Err(msg),
-        };
-        self.tokenizer.eat(1);
-        match token.token_type {
-            TokenType::Integer => return Ok(ast::AstNode::new(ast::NodeType::Integer, token)),
-            TokenType::Symbol => {
-                let next_token = match self.tokenizer.look_ahead(1) {
-                    Ok(token) => token,
-                    Err(msg) => return Err(msg),
-                };
-                match next_token.token_type {
-                    TokenType::LP => return self.function_call(token),
-                    _ => return Ok(ast::AstNode::new(ast::NodeType::Name, token))
-                }
-            },
-            _ => return Err(format!("line:{}, column:{}, syntax error, expect integer or variable or function",
-                    token.row, token.col)),
-        }
-    }
-    fn expression_pow(&mut self) -> Result<ast::AstNode, String> {
-        let mut left = match self.expression_integer_or_name() {
-            Ok(node) => node,
-            Err(msg) => return Err(msg),
-        };
-        loop {
-            let op = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            match op.token_type {
-                TokenType::Pow => {
-                    let mut pow_expr = ast::AstNode::new(ast::NodeType::Pow, op);
-                    self.tokenizer.eat(1);
-                    pow_expr.add_node(left);
-                    pow_expr.add_node(match self.expression_integer_or_name() {
-                        Ok(node) => node,
-                        Err(msg) => return Err(msg),
-                    });
-                    left = pow_expr;
-                },
-                _ => return Ok(left),
-            }
-        }
-    }
-
-    fn expression_mul(&mut self) -> Result<ast::AstNode, String> {
-        let mut left = match self.expression_pow() {
-            Ok(node) => node,
-            Err(msg) => return Err(msg),
-        };
-        loop {
-            let op = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            match op.token_type {
-                TokenType::Mul | TokenType::Div => {
-                    let mut mul_expr = ast::AstNode::new(ast::NodeType::Mul, op);
-                    self.tokenizer.eat(1);
-                    mul_expr.add_node(left);
-                    mul_expr.add_node(match self.expression_pow() {
-                        Ok(node) => node,
-                        Err(msg) => return Err(msg),
-                    });
-                    left = mul_expr;
-                },
-                _ => return Ok(left),
-            }
-        }
-    }
-    fn expression_add(&mut self) -> Result<ast::AstNode, String> {
-        let mut left = match self.expression_mul() {
-            Ok(node) => node,
-            Err(msg) => return Err(msg),
-        };
-        loop {
-            let op = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            match op.token_type {
-                TokenType::Add | TokenType::Sub | TokenType::Mod => {
-                    let mut add_expr = ast::AstNode::new(ast::NodeType::Add, op);
-                    self.tokenizer.eat(1);
-                    add_expr.add_node(left);
-                    add_expr.add_node(match self.expression_mul() {
-                        Ok(node) => node,
-                        Err(msg) => return Err(msg),
-                    });
-                    left = add_expr;
-                },
-                _ => return Ok(left),
-            }
-        }
-    }
-
-    fn expression(&mut self) -> Result<ast::AstNode, String> {
-        return self.expression_add();
-    }
-
-    fn statement_print(&mut self, parent: &mut ast::AstNode) -> Result<(), String> {
-        let mut print_node = ast::AstNode::new(ast::NodeType::Print, match self.tokenizer.look_ahead(1) {
-            Ok(token) => token,
-            Err(msg) => return Err(msg),
-        });
-        self.tokenizer.eat(1);
-        print_node.add_node(match self.expression() {
-            Ok(node) => node,
-            Err(msg) => return Err(msg),
-        });
-        parent.add_node(print_node);
-        return Ok(())
-    }
-
-    fn statement_assign(&mut self, parent: &mut ast::AstNode) -> Result<(), String> {
-        let name = match self.tokenizer.look_ahead(1) {
-            Ok(token) => token,
-            Err(msg) => return Err(msg),
-        };
-        let assign = match self.tokenizer.look_ahead(2) {
-            Ok(token) => token,
-            Err(msg) => return Err(msg),
-        };
-        self.tokenizer.eat(2);
-        match assign.token_type {
-            TokenType::Assign => (),
-            _ => return Err(format!("line:{}, column:{}, syntax error, expect '=', found '{}'",
-                    assign.row, assign.col, assign.literal)),
-        }
-        let mut assign_node = ast::AstNode::new(ast::NodeType::Assign, assign);
-        let name_node = ast::AstNode::new(ast::NodeType::Name, name);
-        assign_node.add_node(name_node);
-        assign_node.add_node(match self.expression() {
-            Ok(node) => node,
-            Err(msg) => return Err(msg),
-        });
-        parent.add_node(assign_node);
-        return Ok(())
-    }
-
-    fn parameters_node(&mut self) -> Result<ast::AstNode, String> {
-        let lp = match self.tokenizer.look_ahead(1) {
-            Ok(token) => token,
-            Err(msg) => return Err(msg),
-        };
-        self.tokenizer.eat(1);
-        match lp.token_type {
-            TokenType::LP => (),
-            _ => return Err(format!("line:{}, column:{}, syntax error, expect '('), found '{}'",
-                lp.row, lp.col, lp.literal)),
-        }
-        let mut param_node = ast::AstNode::new(ast::NodeType::ParamList, lp);
-        loop {
-            let param = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            self.tokenizer.eat(1);
-            match param.token_type {
-                TokenType::Symbol => (),
-                _ => return Err(format!("line:{}, column:{}, syntax error, expect parameter, found '{}'",
-                    param.row, param.col, param.literal)),
-            }
-            param_node.add_node(ast::AstNode::new(ast::NodeType::Param, param));
-            let next_token = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            match next_token.token_type {
-                TokenType::Comma => (),
-                TokenType::RP => break,
-                _ => return Err(format!("line:{}, column:{}, syntax error, expect ',' or ')'), found '{}'",
-                    next_token.row, next_token.col, next_token.literal)),
-            }
-        }
-        Ok(param_node)
-    }
-
-    fn function_body(&mut self) -> Result<ast::AstNode, String> {
-        let lbraceket = match self.tokenizer.look_ahead(1) {
-            Ok(token) => token,
-            Err(msg) => return Err(msg),
-        };
-        self.tokenizer.eat(1);
-        match lbraceket.token_type {
-            TokenType::LBraceket => (),
-            _ => return Err(format!("line:{}, column:{}, syntax error, expect '('), found '{}'",
-                lbraceket.row, lbraceket.col, lbraceket.literal)),
-        }
-        let mut body = ast::AstNode::new(ast::NodeType::FuncBody, lbraceket);
-        loop {
-            let token = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            } ;
-            match token.token_type {
-                TokenType::Print => {
-                    let result = self.statement_print(&mut body);
-                    if result.is_err() {
-                        return Err(result.unwrap_err());
-                    }
-                },
-                TokenType::Symbol => {
-                    let result = self.statement_assign(&mut body);
-                    if result.is_err() {
-                        return Err(result.unwrap_err());
-                    }
-                },
-                TokenType::Return => {
-                    let mut ret = ast::AstNode::new(ast::NodeType::Return, token);
-                    self.tokenizer.eat(1);
-                    let expr = match self.expression() {
-                        Ok(token) => token,
-                        Err(msg) => return Err(msg),
-                    };
-                    ret.add_node(expr);
-                    body.add_node(ret);
-                    return Ok(body);
-                }
-                TokenType::Newline => { self.tokenizer.eat(1); continue; },
-                _ => return Err(format!("line:{}, column:{}, syntax error, expect 'print' or 'variable'",
-                        token.row, token.col)),
-            }
-        }
-    }
-
-    fn statement_func_decl(&mut self, parent: &mut ast::AstNode) -> Result<(), String> {
-        let func_name = match self.tokenizer.look_ahead(2) {
-            Ok(token) => token,
-            Err(msg) => return Err(msg),
-        };
-        self.tokenizer.eat(2);
-        match func_name.token_type {
-            TokenType::Symbol => (),
-            _ => return Err(format!("line:{}, column:{}, syntax error, expect function name, found '{}'",
-            func_name.row, func_name.col, func_name.literal)),
-        }
-        let mut func_decl_node = ast::AstNode::new(ast::NodeType::FuncDecl, func_name);
-        let parameters_node = match self.parameters_node() {
-            Ok(node) => node,
-            Err(msg) => return Err(msg),
-        };
-        let func_body = match self.function_body() {
-            Ok(node) => node,
-            Err(msg) => return Err(msg),
-        };
-        func_decl_node.add_node(parameters_node);
-        func_decl_node.add_node(func_body);
-        parent.add_node(func_decl_node);
-        Ok(())
-    }
-
-    pub fn parse(&mut self) -> Result<ast::AstNode, String> {
-        let mut ast_root = ast::AstNode::new(ast::NodeType::Root, Token::new());
-        loop {
-            let token = match self.tokenizer.look_ahead(1) {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            match token.token_type {
-                TokenType::Print => {
-                    let result = self.statement_print(&mut ast_root);
-                    if result.is_err() {
-                        return Err(result.unwrap_err());
-                    }
-                },
-                TokenType::Symbol => {
-                    let result = self.statement_assign(&mut ast_root);
-                    if result.is_err() {
-                        return Err(result.unwrap_err());
-                    }
-                },
-                TokenType::FuncDecl => {
-                    let result = self.statement_func_decl(&mut ast_root);
-                    if result.is_err() {
-                        return Err(result.unwrap_err());
-                    }
-                },
-                TokenType::Newline => { self.tokenizer.eat(1); continue; },
-                TokenType::EOF => return Ok(ast_root),
-                _ => return Err(format!("line:{}, column:{}, syntax error, expect 'print' or 'variable'",
-                        token.row, token.col)),
-            }
-        }
-    }
-
-    pub fn new(tokenizer: Tokenizer) -> Parser {
-        return Parser {
-            tokenizer: tokenizer
-        }
-    }
-}
+use super::tokenizer::*;
+use super::ast;
+use super::syntax::{self, Child, Node, NodeType};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Turns the concrete syntax tree that the parser generated from
+/// `grammar.txt` builds into the AST. Checks the grammar can't express are
+/// done here: operator precedence, the context of `break`, `continue`,
+/// `return` and operator declarations, assignment targets and parameter
+/// lists.
+pub struct Parser {
+    tokenizer: Tokenizer,
+    /// The tokens of the source, `Child::Token` refers to them by index.
+    tokens: Vec<Token>,
+    /// Enclosing loops of the statement being parsed, with their labels.
+    loops: Vec<Option<String>>,
+    function_depth: usize,
+    /// Binding power and associativity of every infix operator.
+    infix_operators: HashMap<String, (i32, Assoc)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Assoc {
+    Left,
+    Right,
+    /// `a < b < c` and `a..b..c` are syntax errors.
+    None,
+}
+
+/// Binding power and associativity of the built-in infix operators.
+pub const INFIX_OPERATORS: [(&str, i32, Assoc); 16] = [
+    ("..", 2, Assoc::None), ("..=", 2, Assoc::None),
+    ("or", 4, Assoc::Left),
+    ("and", 6, Assoc::Left),
+    ("<", 10, Assoc::None), (">", 10, Assoc::None), ("<=", 10, Assoc::None),
+    (">=", 10, Assoc::None), ("==", 10, Assoc::None), ("!=", 10, Assoc::None),
+    ("+", 20, Assoc::Left), ("-", 20, Assoc::Left), ("%", 20, Assoc::Left),
+    ("*", 40, Assoc::Left), ("/", 40, Assoc::Left),
+    ("^", 50, Assoc::Right),
+];
+
+/// Calls, indexing and member access bind tighter than any infix operator,
+/// user defined ones included.
+pub const POSTFIX_BINDING_POWER: i32 = 110;
+
+/// Syntax errors list what was expected when it is no more than this.
+const MAX_EXPECTED: usize = 4;
+
+lazy_static! {
+    /// The token type of the generated parser for every `TokenType`.
+    static ref GRAMMAR_TOKENS: Vec<(TokenType, syntax::TokenType)> = syntax::TOKENS.iter()
+        .filter_map(|token| grammar_token(syntax::name(*token)).map(|token_type| (token_type, *token)))
+        .collect();
+}
+
+fn grammar_token_type(token_type: TokenType) -> syntax::TokenType {
+    match GRAMMAR_TOKENS.iter().find(|(t, _)| *t == token_type) {
+        Some((_, token)) => *token,
+        None => panic!("{:?} is not a token of grammar.txt", token_type),
+    }
+}
+
+/// An expression as the grammar has it: operands with their prefix
+/// operators and the infix operators between them, in source order.
+enum Item<'a> {
+    Prefix(Token),
+    /// A `POSTFIX` node.
+    Operand(&'a Node),
+    Infix(Token),
+}
+
+/// The child nodes of `node`, without its tokens.
+fn nodes(node: &Node) -> Vec<&Node> {
+    node.childs.iter().filter_map(|child| match child {
+        Child::Node(node) => Some(node),
+        Child::Token(_) => None,
+    }).collect()
+}
+
+/// Index of the first token of a node that is not empty.
+fn first_token(node: &Node) -> usize {
+    match &node.childs[0] {
+        Child::Token(index) => *index,
+        Child::Node(child) => first_token(child),
+    }
+}
+
+impl Parser {
+    fn token(&self, index: usize) -> Token {
+        self.tokens[index].clone()
+    }
+
+    /// The tokens among the children of `node`.
+    fn child_tokens(&self, node: &Node) -> Vec<Token> {
+        node.childs.iter().filter_map(|child| match child {
+            Child::Token(index) => Some(self.token(*index)),
+            Child::Node(_) => None,
+        }).collect()
+    }
+
+    fn expected_text(token: syntax::TokenType) -> String {
+        match syntax::name(token) {
+            "NAME" => "name".to_string(),
+            "INTEGER" => "integer".to_string(),
+            "STRING" => "string".to_string(),
+            "NEWLINE" => "line break".to_string(),
+            "CUSTOM_OPERATOR" => "operator".to_string(),
+            "EOF" => "end of file".to_string(),
+            _ => format!("'{}'", syntax::literal(token).or_else(|| syntax::pattern(token)).unwrap_or_default()),
+        }
+    }
+
+    fn syntax_error(&self, error: syntax::SyntaxError) -> String {
+        let token = &self.tokens[error.pos];
+        let found = match token.token_type {
+            TokenType::Eof => "end of file".to_string(),
+            TokenType::Newline => "line break".to_string(),
+            _ => format!("'{}'", token.literal),
+        };
+        let expected: Vec<String> = error.expected.into_iter().map(Parser::expected_text).collect();
+        match expected.split_last() {
+            Some((last, rest)) if expected.len() <= MAX_EXPECTED => {
+                let expected = match rest.is_empty() {
+                    true => last.clone(),
+                    false => format!("{} or {}", rest.join(", "), last),
+                };
+                format!("line:{}, column:{}, syntax error, expect {}, found {}", token.row, token.col, expected, found)
+            },
+            _ => format!("line:{}, column:{}, syntax error, unexpected {}", token.row, token.col, found),
+        }
+    }
+
+    /// The name a node consists of, e.g. the `x` of an expression that is
+    /// just `x`.
+    fn bare_name(&self, mut node: &Node) -> Option<Token> {
+        loop {
+            match node.childs.as_slice() {
+                [Child::Node(child)] => node = child,
+                [Child::Token(index)] if self.tokens[*index].token_type == TokenType::Symbol => return Some(self.token(*index)),
+                _ => return None,
+            }
+        }
+    }
+
+    /// The `func NAME (...) {...}` primary of an expression list that is
+    /// nothing else, which makes it a function declaration.
+    fn declared_function<'a>(&self, mut node: &'a Node) -> Option<&'a Node> {
+        while let [Child::Node(child)] = node.childs.as_slice() {
+            node = child;
+        }
+        match node.childs.as_slice() {
+            [Child::Token(keyword), Child::Token(_), ..] if self.tokens[*keyword].token_type == TokenType::FuncDecl => Some(node),
+            _ => None,
+        }
+    }
+
+    /// The call node takes the callee's token so that errors point at the
+    /// function name for the common `name(args)` case.
+    fn function_call<'a>(&mut self, callee: ast::Expr, childs: &mut impl Iterator<Item = &'a Child>) -> Result<ast::Expr, String> {
+        let mut args = Vec::<ast::Arg>::new();
+        for child in childs {
+            let argument = match child {
+                Child::Node(argument) => argument,
+                Child::Token(index) if self.tokens[*index].token_type == TokenType::RP => break,
+                Child::Token(_) => continue,
+            };
+            let parts = nodes(argument);
+            if let [name, value] = parts.as_slice() {
+                let name = match self.bare_name(name) {
+                    Some(name) => name,
+                    None => {
+                        let token = &self.tokens[first_token(name)];
+                        return Err(format!("line:{}, column:{}, syntax error, expect argument name before ':'",
+                            token.row, token.col));
+                    },
+                };
+                args.push(ast::Arg::Named { name, value: self.expression(value)? });
+                continue;
+            }
+            let expr = self.expression(parts[0])?;
+            let named = args.iter().find_map(|arg| match arg {
+                ast::Arg::Named { name, .. } => Some(name),
+                ast::Arg::Positional(_) => None,
+            });
+            if let Some(named) = named {
+                return Err(format!("line:{}, column:{}, syntax error, positional argument follows named argument '{}'",
+                    expr.token().row, expr.token().col, named.literal));
+            }
+            args.push(ast::Arg::Positional(expr));
+        }
+        Ok(ast::Expr::Call { token: callee.token().clone(), callee: Box::new(callee), args })
+    }
+
+    /// The expressions of `ITEMS` or `PAIRS`, which nest for every comma,
+    /// and whether there is a comma.
+    fn items(&mut self, mut node: &Node) -> Result<(Vec<ast::Expr>, bool), String> {
+        let mut items = Vec::<ast::Expr>::new();
+        let mut comma = false;
+        loop {
+            let mut rest = None;
+            for child in &node.childs {
+                match child {
+                    Child::Node(child) if child.node_type == node.node_type => rest = Some(child),
+                    Child::Node(expr) => items.push(self.expression(expr)?),
+                    Child::Token(index) => comma |= self.tokens[*index].token_type == TokenType::Comma,
+                }
+            }
+            match rest {
+                Some(rest) => node = rest,
+                None => return Ok((items, comma)),
+            }
+        }
+    }
+
+    fn expression_primary(&mut self, node: &Node) -> Result<ast::Expr, String> {
+        let token = self.token(first_token(node));
+        match token.token_type {
+            TokenType::Integer => match token.literal.parse::<i64>() {
+                Ok(value) => Ok(ast::Expr::Integer { token, value }),
+                Err(_) => Err(format!("line:{}, column:{}, syntax error, integer literal {} is too large",
+                    token.row, token.col, token.literal)),
+            },
+            TokenType::Str => Ok(ast::Expr::Str { token }),
+            TokenType::Symbol => Ok(ast::Expr::Name { token }),
+            TokenType::LSquare => Ok(ast::Expr::List { token, items: self.items(nodes(node)[0])?.0 }),
+            // `{` in expression position always starts a map literal, blocks
+            // only follow the headers of loops and functions.
+            TokenType::LBraceket => {
+                let mut items = self.items(nodes(node)[0])?.0.into_iter();
+                let mut pairs = Vec::<(ast::Expr, ast::Expr)>::new();
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    pairs.push((key, value));
+                }
+                Ok(ast::Expr::Map { token, pairs })
+            },
+            // `(expr)` only groups, a comma makes it a tuple: `()`, `(a,)`, `(a, b)`.
+            TokenType::LP => match self.items(nodes(node)[0])? {
+                (mut items, false) if items.len() == 1 => Ok(items.pop().unwrap()),
+                (items, _) => Ok(ast::Expr::Tuple { token, items }),
+            },
+            // Anonymous function: `func(x) { return x + n }`
+            _ => match self.child_tokens(node).get(1) {
+                Some(name) => Err(format!("line:{}, column:{}, syntax error, function '{}' can only be declared as a statement",
+                    name.row, name.col, name.literal)),
+                None => {
+                    let parts = nodes(node);
+                    Ok(ast::Expr::Lambda { token, function: self.function_def(parts[0], parts[1])? })
+                },
+            },
+        }
+    }
+
+    /// Binding power of prefix operators, the operand is parsed with it.
+    pub fn prefix_binding_power(token: &Token) -> Option<i32> {
+        match token.token_type {
+            TokenType::Not => Some(8),
+            TokenType::Sub => Some(45),
+            _ => None,
+        }
+    }
+
+    /// User defined operators are added to the table as their declarations
+    /// are parsed.
+    fn infix_binding_power(&self, token: &Token) -> Result<(i32, Assoc), String> {
+        match self.infix_operators.get(&token.literal) {
+            Some(binding) => Ok(*binding),
+            None => Err(format!("line:{}, column:{}, syntax error, operator '{}' is not declared",
+                token.row, token.col, token.literal)),
+        }
+    }
+
+    /// A primary expression followed by calls, indexing and member access.
+    fn expression_postfix(&mut self, node: &Node) -> Result<ast::Expr, String> {
+        let mut childs = node.childs.iter();
+        let mut left = match childs.next() {
+            Some(Child::Node(primary)) => self.expression_primary(primary)?,
+            _ => unreachable!("POSTFIX starts with PRIMARY"),
+        };
+        while let Some(child) = childs.next() {
+            let token = match child {
+                Child::Token(index) => self.token(*index),
+                Child::Node(_) => unreachable!("postfix operators start with a token"),
+            };
+            left = match (token.token_type, childs.next()) {
+                (TokenType::LSquare, Some(Child::Node(index))) => {
+                    let index = Box::new(self.expression(index)?);
+                    childs.next();
+                    ast::Expr::Index { token, target: Box::new(left), index }
+                },
+                (TokenType::Dot, Some(Child::Token(name))) => ast::Expr::Member { token, target: Box::new(left), name: self.token(*name) },
+                (_, Some(Child::Token(index))) if self.tokens[*index].token_type == TokenType::RP => self.function_call(left, &mut std::iter::empty())?,
+                (_, Some(argument)) => self.function_call(left, &mut std::iter::once(argument).chain(&mut childs))?,
+                (_, None) => unreachable!("postfix operators are complete"),
+            };
+        }
+        Ok(left)
+    }
+
+    /// Adds the operands and operators of a `UNARY` node to `items`.
+    fn unary_items<'a>(&self, mut node: &'a Node, items: &mut Vec<Item<'a>>) {
+        loop {
+            match node.childs.as_slice() {
+                [Child::Token(op), Child::Node(operand)] => {
+                    items.push(Item::Prefix(self.token(*op)));
+                    node = operand;
+                },
+                _ => return items.push(Item::Operand(nodes(node)[0])),
+            }
+        }
+    }
+
+    /// Pratt parser over the items of an expression: parses the expression
+    /// at `pos` whose operators all bind at least as tight as
+    /// `min_binding_power`.
+    fn expression_bp(&mut self, items: &[Item], pos: &mut usize, min_binding_power: i32) -> Result<ast::Expr, String> {
+        let mut left = match &items[*pos] {
+            Item::Prefix(token) => {
+                *pos += 1;
+                let binding_power = Parser::prefix_binding_power(token).unwrap_or(0);
+                let op = match token.token_type {
+                    TokenType::Not => ast::UnaryOp::Not,
+                    _ => ast::UnaryOp::Neg,
+                };
+                let operand = Box::new(self.expression_bp(items, pos, binding_power)?);
+                ast::Expr::Unary { op, token: token.clone(), operand }
+            },
+            Item::Operand(node) => {
+                *pos += 1;
+                self.expression_postfix(node)?
+            },
+            Item::Infix(_) => unreachable!("the grammar puts an operand before every infix operator"),
+        };
+        while let Some(Item::Infix(op)) = items.get(*pos) {
+            let (binding_power, assoc) = self.infix_binding_power(op)?;
+            if binding_power < min_binding_power {
+                break;
+            }
+            *pos += 1;
+            let right = match assoc {
+                Assoc::Right => self.expression_bp(items, pos, binding_power)?,
+                Assoc::Left | Assoc::None => self.expression_bp(items, pos, binding_power + 1)?,
+            };
+            if let (Assoc::None, Some(Item::Infix(next))) = (assoc, items.get(*pos)) {
+                if self.infix_binding_power(next)?.0 == binding_power {
+                    return Err(format!("line:{}, column:{}, syntax error, '{}' cannot be chained with '{}', use parentheses",
+                        next.row, next.col, next.literal, op.literal));
+                }
+            }
+            let binary_op = match op.token_type {
+                TokenType::Add => ast::BinaryOp::Add,
+                TokenType::Sub => ast::BinaryOp::Sub,
+                TokenType::Mul => ast::BinaryOp::Mul,
+                TokenType::Div => ast::BinaryOp::Div,
+                TokenType::Mod => ast::BinaryOp::Mod,
+                TokenType::Pow => ast::BinaryOp::Pow,
+                TokenType::Lt => ast::BinaryOp::Lt,
+                TokenType::Gt => ast::BinaryOp::Gt,
+                TokenType::Le => ast::BinaryOp::Le,
+                TokenType::Ge => ast::BinaryOp::Ge,
+                TokenType::Eq => ast::BinaryOp::Eq,
+                TokenType::Ne => ast::BinaryOp::Ne,
+                TokenType::And => ast::BinaryOp::And,
+                TokenType::Or => ast::BinaryOp::Or,
+                TokenType::DotDot => ast::BinaryOp::Range,
+                TokenType::DotDotEq => ast::BinaryOp::RangeInclusive,
+                _ => ast::BinaryOp::Custom(op.literal.clone()),
+            };
+            left = ast::Expr::Binary { op: binary_op, token: op.clone(), lhs: Box::new(left), rhs: Box::new(right) };
+        }
+        Ok(left)
+    }
+
+    fn expression(&mut self, node: &Node) -> Result<ast::Expr, String> {
+        let mut items = Vec::<Item>::new();
+        for child in nodes(node) {
+            match child.node_type {
+                NodeType::InfixOperator => items.push(Item::Infix(self.token(first_token(child)))),
+                _ => self.unary_items(child, &mut items),
+            }
+        }
+        self.expression_bp(&items, &mut 0, 0)
+    }
+
+    /// Comma separated expressions without parentheses, e.g. `b, a` in
+    /// `a, b = b, a`. More than one expression forms a tuple.
+    fn expression_list(&mut self, node: &Node) -> Result<ast::Expr, String> {
+        let mut items = Vec::<ast::Expr>::new();
+        for expr in nodes(node) {
+            items.push(self.expression(expr)?);
+        }
+        if items.len() == 1 {
+            return Ok(items.pop().unwrap());
+        }
+        let token = items[0].token().clone();
+        Ok(ast::Expr::Tuple { token, items })
+    }
+
+    fn assign_target(target: ast::Expr) -> Result<ast::Target, String> {
+        match target {
+            ast::Expr::Name { token } => Ok(ast::Target::Name { token }),
+            ast::Expr::Index { token, target, index } => Ok(ast::Target::Index { token, target: *target, index: *index }),
+            ast::Expr::Member { token, target, name } => Ok(ast::Target::Member { token, target: *target, name }),
+            ast::Expr::Tuple { token, items } if !items.is_empty() => {
+                let items = items.into_iter().map(Parser::assign_target).collect::<Result<Vec<ast::Target>, String>>()?;
+                Ok(ast::Target::Tuple { token, items })
+            },
+            _ => Err(format!("line:{}, column:{}, syntax error, invalid assignment target",
+                    target.token().row, target.token().col)),
+        }
+    }
+
+    /// An expression list is an assignment target when `=` follows it, a
+    /// loop label when `:` does, otherwise an expression statement.
+    fn statement_expression(&mut self, node: &Node) -> Result<ast::Stmt, String> {
+        let parts = nodes(node);
+        match self.child_tokens(node).first() {
+            Some(token) if token.token_type == TokenType::Assign => {
+                let target = Parser::assign_target(self.expression_list(parts[0])?)?;
+                Ok(ast::Stmt::Assign { token: token.clone(), target, value: self.expression_list(parts[1])? })
+            },
+            Some(_) => self.statement_labeled_loop(parts[0], parts[1]),
+            None => match self.declared_function(parts[0]) {
+                Some(primary) => self.statement_func_decl(primary),
+                None => Ok(ast::Stmt::Expression { value: self.expression_list(parts[0])? }),
+            },
+        }
+    }
+
+    /// The statements of a `BLOCK` node.
+    fn block(&mut self, node: &Node) -> Result<Vec<ast::Stmt>, String> {
+        self.statements(nodes(node)[0])
+    }
+
+    fn loop_body(&mut self, node: &Node, label: &Option<Token>) -> Result<Vec<ast::Stmt>, String> {
+        self.loops.push(label.as_ref().map(|label| label.literal.clone()));
+        let body = self.block(node);
+        self.loops.pop();
+        body
+    }
+
+    /// `while cond {...}` or `for target in iterable {...}`
+    fn statement_loop(&mut self, node: &Node, label: Option<Token>) -> Result<ast::Stmt, String> {
+        let token = self.token(first_token(node));
+        let parts = nodes(node);
+        match token.token_type {
+            TokenType::While => {
+                let condition = self.expression(parts[0])?;
+                let body = self.loop_body(parts[1], &label)?;
+                Ok(ast::Stmt::While { token, label, condition, body })
+            },
+            _ => {
+                let target = Parser::assign_target(self.expression_list(parts[0])?)?;
+                let iterable = self.expression(parts[1])?;
+                let body = self.loop_body(parts[2], &label)?;
+                Ok(ast::Stmt::For { token, label, target, iterable, body })
+            },
+        }
+    }
+
+    /// `outer: for ...` or `outer: while ...`
+    fn statement_labeled_loop(&mut self, label: &Node, node: &Node) -> Result<ast::Stmt, String> {
+        let name = match self.bare_name(label) {
+            Some(name) => name,
+            None => {
+                let token = &self.tokens[first_token(label)];
+                return Err(format!("line:{}, column:{}, syntax error, a loop label must be a name", token.row, token.col));
+            },
+        };
+        if self.loops.contains(&Some(name.literal.clone())) {
+            return Err(format!("line:{}, column:{}, syntax error, label '{}' shadows an enclosing loop label",
+                name.row, name.col, name.literal));
+        }
+        self.statement_loop(node, Some(name))
+    }
+
+    /// `break`/`continue` with an optional label, only valid inside a loop.
+    fn statement_loop_control(&mut self, node: &Node) -> Result<ast::Stmt, String> {
+        let mut tokens = self.child_tokens(node).into_iter();
+        let keyword = tokens.next().unwrap();
+        if self.loops.is_empty() {
+            return Err(format!("line:{}, column:{}, syntax error, '{}' outside of a loop",
+                keyword.row, keyword.col, keyword.literal));
+        }
+        let label = tokens.next();
+        if let Some(name) = &label {
+            if !self.loops.contains(&Some(name.literal.clone())) {
+                return Err(format!("line:{}, column:{}, syntax error, unknown loop label '{}'",
+                    name.row, name.col, name.literal));
+            }
+        }
+        match keyword.token_type {
+            TokenType::Break => Ok(ast::Stmt::Break { token: keyword, label }),
+            _ => Ok(ast::Stmt::Continue { token: keyword, label }),
+        }
+    }
+
+    /// `return` with an optional value, allowed anywhere inside a function.
+    fn statement_return(&mut self, node: &Node) -> Result<ast::Stmt, String> {
+        let keyword = self.token(first_token(node));
+        if self.function_depth == 0 {
+            return Err(format!("line:{}, column:{}, syntax error, 'return' outside of a function",
+                keyword.row, keyword.col));
+        }
+        let value = match nodes(node).first() {
+            Some(list) => Some(self.expression_list(list)?),
+            None => None,
+        };
+        Ok(ast::Stmt::Return { token: keyword, value })
+    }
+
+    /// `operator infix <+> precedence 60 (a, b) { ... }`, only at top level.
+    /// The operator is usable in the rest of the file once declared.
+    fn statement_operator_decl(&mut self, node: &Node) -> Result<ast::Stmt, String> {
+        let tokens = self.child_tokens(node);
+        let (keyword, fixity, op, precedence_keyword, precedence) = (&tokens[0], &tokens[1], &tokens[2], &tokens[3], &tokens[4]);
+        if self.function_depth > 0 || !self.loops.is_empty() {
+            return Err(format!("line:{}, column:{}, syntax error, operators can only be declared at top level",
+                keyword.row, keyword.col));
+        }
+        if fixity.literal != "infix" {
+            return Err(format!("line:{}, column:{}, syntax error, expect 'infix', found '{}'",
+                fixity.row, fixity.col, fixity.literal));
+        }
+        if self.infix_operators.contains_key(&op.literal) {
+            return Err(format!("line:{}, column:{}, syntax error, operator '{}' is already defined",
+                op.row, op.col, op.literal));
+        }
+        if precedence_keyword.literal != "precedence" {
+            return Err(format!("line:{}, column:{}, syntax error, expect 'precedence', found '{}'",
+                precedence_keyword.row, precedence_keyword.col, precedence_keyword.literal));
+        }
+        let value = match precedence.literal.parse::<i32>() {
+            Ok(value) if (1..=100).contains(&value) => value,
+            _ => return Err(format!("line:{}, column:{}, syntax error, precedence must be between 1 and 100",
+                precedence.row, precedence.col)),
+        };
+        let parts = nodes(node);
+        let params = self.parameters(parts[0])?;
+        let plain = params.iter().all(|param| matches!(param, ast::Param::Required { .. }));
+        if params.len() != 2 || !plain {
+            let lp = &self.tokens[first_token(parts[0])];
+            return Err(format!("line:{}, column:{}, syntax error, infix operator '{}' takes exactly two parameters",
+                lp.row, lp.col, op.literal));
+        }
+        self.infix_operators.insert(op.literal.clone(), (value, Assoc::Left));
+        let body = self.function_body(parts[1])?;
+        Ok(ast::Stmt::OperatorDecl { op: op.clone(), precedence: value, function: Rc::new(ast::FuncDef { params, body }) })
+    }
+
+    /// Statements allowed in any statement list: top level, function bodies
+    /// and loop bodies.
+    fn statement(&mut self, node: &Node) -> Result<ast::Stmt, String> {
+        if let Some(Child::Node(child)) = node.childs.first() {
+            return match child.node_type {
+                NodeType::Loop => self.statement_loop(child, None),
+                NodeType::OperatorDeclaration => self.statement_operator_decl(child),
+                _ => self.statement_expression(node),
+            };
+        }
+        let token = self.token(first_token(node));
+        match token.token_type {
+            TokenType::Print => Ok(ast::Stmt::Print { token, value: self.expression(nodes(node)[0])? }),
+            TokenType::Break | TokenType::Continue => self.statement_loop_control(node),
+            _ => self.statement_return(node),
+        }
+    }
+
+    /// The statements of a `STATEMENTS` node.
+    fn statements(&mut self, node: &Node) -> Result<Vec<ast::Stmt>, String> {
+        let mut statements = Vec::<ast::Stmt>::new();
+        for statement in nodes(node) {
+            statements.push(self.statement(statement)?);
+        }
+        Ok(statements)
+    }
+
+    /// `(a, b = 2, ...rest)`: parameters with a default value must follow the
+    /// required ones and the variadic parameter comes last.
+    fn parameters(&mut self, node: &Node) -> Result<Vec<ast::Param>, String> {
+        let mut params = Vec::<ast::Param>::new();
+        let mut has_default = false;
+        for child in &node.childs {
+            let param = match child {
+                Child::Node(param) => param,
+                Child::Token(index) => {
+                    let token = &self.tokens[*index];
+                    if token.token_type == TokenType::Comma && matches!(params.last(), Some(ast::Param::Variadic { .. })) {
+                        return Err(format!("line:{}, column:{}, syntax error, variadic parameter must be the last parameter",
+                            token.row, token.col));
+                    }
+                    continue;
+                },
+            };
+            let tokens = self.child_tokens(param);
+            let variadic = tokens[0].token_type == TokenType::Ellipsis;
+            let name = tokens[variadic as usize].clone();
+            if params.iter().any(|p| p.name().literal == name.literal) {
+                return Err(format!("line:{}, column:{}, syntax error, duplicate parameter '{}'",
+                    name.row, name.col, name.literal));
+            }
+            if variadic {
+                params.push(ast::Param::Variadic { name });
+            } else if let Some(default) = nodes(param).first() {
+                params.push(ast::Param::Optional { name, default: self.expression(default)? });
+                has_default = true;
+            } else if has_default {
+                return Err(format!("line:{}, column:{}, syntax error, parameter '{}' without default follows a parameter with default",
+                    name.row, name.col, name.literal));
+            } else {
+                params.push(ast::Param::Required { name });
+            }
+        }
+        Ok(params)
+    }
+
+    /// Loops outside of the function are not visible to `break`/`continue`
+    /// inside of it.
+    fn function_body(&mut self, node: &Node) -> Result<Vec<ast::Stmt>, String> {
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        self.function_depth += 1;
+        let body = self.block(node);
+        self.function_depth -= 1;
+        self.loops = enclosing_loops;
+        body
+    }
+
+    fn function_def(&mut self, parameters: &Node, block: &Node) -> Result<Rc<ast::FuncDef>, String> {
+        let params = self.parameters(parameters)?;
+        let body = self.function_body(block)?;
+        Ok(Rc::new(ast::FuncDef { params, body }))
+    }
+
+    fn statement_func_decl(&mut self, primary: &Node) -> Result<ast::Stmt, String> {
+        let name = self.child_tokens(primary)[1].clone();
+        let parts = nodes(primary);
+        Ok(ast::Stmt::FuncDecl { name, function: self.function_def(parts[0], parts[1])? })
+    }
+
+    /// Top-level functions are hoisted, so each name may be defined only once.
+    pub fn parse(&mut self) -> Result<Vec<ast::Stmt>, String> {
+        self.tokens = self.tokenizer.tokens()?;
+        let token_types: Vec<syntax::TokenType> = self.tokens.iter().map(|token| grammar_token_type(token.token_type)).collect();
+        let tree = syntax::parse(&token_types).map_err(|error| self.syntax_error(error))?;
+        let statements = self.statements(nodes(&tree)[0])?;
+        let mut functions = HashMap::<&str, &Token>::new();
+        for statement in &statements {
+            if let ast::Stmt::FuncDecl { name, .. } = statement {
+                if let Some(previous) = functions.insert(&name.literal, name) {
+                    return Err(format!("line:{}, column:{}, syntax error, function '{}' is already defined at line:{}, column:{}",
+                        name.row, name.col, name.literal, previous.row, previous.col));
+                }
+            }
+        }
+        Ok(statements)
+    }
+
+    /// Parses source that is a single expression list, e.g. `f(1)` or `a, b`.
+    pub fn parse_expression(&mut self) -> Result<ast::Expr, String> {
+        let mut statements = self.parse()?;
+        match (statements.pop(), statements.is_empty()) {
+            (Some(ast::Stmt::Expression { value }), true) => Ok(value),
+            _ => Err("not an expression".to_string()),
+        }
+    }
+
+    /// Makes an operator declared in previously parsed code known, used by
+    /// the REPL which parses every input on its own.
+    pub fn declare_operator(&mut self, op: &str, precedence: i32) {
+        self.tokenizer.add_operator(op);
+        self.infix_operators.insert(op.to_string(), (precedence, Assoc::Left));
+    }
+
+    pub fn new(tokenizer: Tokenizer) -> Parser {
+        Parser {
+            tokenizer,
+            tokens: Vec::new(),
+            loops: Vec::new(),
+            function_depth: 0,
+            infix_operators: INFIX_OPERATORS.iter().map(|(op, binding_power, assoc)| (op.to_string(), (*binding_power, *assoc))).collect(),
+        }
+    }
+}
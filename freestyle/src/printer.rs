@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use super::ast::{Arg, BinaryOp, Expr, FuncDef, Param, Stmt, Target};
+use super::formatter::quote;
+use super::parser::{Assoc, Parser, INFIX_OPERATORS, POSTFIX_BINDING_POWER};
+use super::tokenizer::{Token, TokenType};
+
+const INDENT: &str = "    ";
+
+/// Prints an AST back to source code that parses to the same AST. Only the
+/// parentheses required by binding power and associativity are printed,
+/// blocks are laid out as `freestyle fmt` does.
+struct Printer {
+    operators: HashMap<String, (i32, Assoc)>,
+    out: String,
+    depth: usize,
+}
+
+/// Whether a tuple was written in parentheses. A tuple without them takes
+/// the token of its first item, which may be a `(` as well.
+fn parenthesized(token: &Token, first: Option<&Token>) -> bool {
+    token.token_type == TokenType::LP && first.is_none_or(|first| (first.row, first.col) != (token.row, token.col))
+}
+
+impl Printer {
+    fn binding(&self, op: &BinaryOp) -> (i32, Assoc) {
+        self.operators.get(op.as_str()).copied().unwrap_or((0, Assoc::Left))
+    }
+
+    /// Prints `expr` where the parser reads an expression with
+    /// `min_binding_power`, followed by an infix operator binding with
+    /// `next_binding_power`, 0 when nothing follows.
+    fn expr_in(&self, expr: &Expr, min_binding_power: i32, next_binding_power: i32) -> String {
+        let parenthesize = match expr {
+            Expr::Binary { op, .. } => self.binding(op).0 < min_binding_power,
+            // The operand of a prefix operator takes every operator binding at
+            // least as tight as the prefix operator.
+            Expr::Unary { token, .. } => match Parser::prefix_binding_power(token) {
+                Some(binding_power) => next_binding_power >= binding_power,
+                None => false,
+            },
+            Expr::Tuple { token, items } => !parenthesized(token, items.first().map(Expr::token)) && min_binding_power > 0,
+            _ => false,
+        };
+        match parenthesize {
+            true => format!("({})", self.expr_bare(expr, 0)),
+            false => self.expr_bare(expr, next_binding_power),
+        }
+    }
+
+    fn expr(&self, expr: &Expr) -> String {
+        self.expr_in(expr, 0, 0)
+    }
+
+    fn items(&self, items: &[Expr]) -> String {
+        items.iter().map(|item| self.expr(item)).collect::<Vec<String>>().join(", ")
+    }
+
+    fn postfix_target(&self, target: &Expr) -> String {
+        self.expr_in(target, POSTFIX_BINDING_POWER, POSTFIX_BINDING_POWER)
+    }
+
+    fn expr_bare(&self, expr: &Expr, next_binding_power: i32) -> String {
+        match expr {
+            Expr::Integer { token, value } => match token.literal.parse::<i64>() {
+                Ok(parsed) if parsed == *value => token.literal.clone(),
+                _ => value.to_string(),
+            },
+            Expr::Str { token } => quote(&token.literal),
+            Expr::Name { token } => token.literal.clone(),
+            Expr::List { items, .. } => format!("[{}]", self.items(items)),
+            // `a, b` without parentheses only appears as a whole expression list.
+            Expr::Tuple { token, items } if !parenthesized(token, items.first().map(Expr::token)) => self.items(items),
+            Expr::Tuple { items, .. } if items.len() == 1 => format!("({},)", self.expr(&items[0])),
+            Expr::Tuple { items, .. } => format!("({})", self.items(items)),
+            Expr::Map { pairs, .. } => {
+                let pairs: Vec<String> = pairs.iter()
+                    .map(|(key, value)| format!("{}: {}", self.expr(key), self.expr(value)))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            },
+            Expr::Index { target, index, .. } => format!("{}[{}]", self.postfix_target(target), self.expr(index)),
+            Expr::Member { target, name, .. } => format!("{}.{}", self.postfix_target(target), name.literal),
+            Expr::Call { callee, args, .. } => {
+                let args: Vec<String> = args.iter().map(|arg| match arg {
+                    Arg::Positional(value) => self.expr(value),
+                    Arg::Named { name, value } => format!("{}: {}", name.literal, self.expr(value)),
+                }).collect();
+                format!("{}({})", self.postfix_target(callee), args.join(", "))
+            },
+            Expr::Lambda { function, .. } => format!("func {}", self.function(function)),
+            Expr::Unary { token, operand, .. } => {
+                let binding_power = Parser::prefix_binding_power(token).unwrap_or(0);
+                let operand = self.expr_in(operand, binding_power, next_binding_power);
+                match token.token_type {
+                    // `--a` would lex as one token once `--` is declared.
+                    TokenType::Sub if operand.starts_with('-') => format!("- {}", operand),
+                    TokenType::Sub => format!("-{}", operand),
+                    _ => format!("{} {}", token.literal, operand),
+                }
+            },
+            Expr::Binary { op, lhs, rhs, .. } => {
+                let (binding_power, assoc) = self.binding(op);
+                let (lhs_min, rhs_min) = match assoc {
+                    Assoc::Left => (binding_power, binding_power + 1),
+                    Assoc::Right => (binding_power + 1, binding_power),
+                    Assoc::None => (binding_power + 1, binding_power + 1),
+                };
+                let lhs = self.expr_in(lhs, lhs_min, binding_power);
+                let rhs = self.expr_in(rhs, rhs_min, next_binding_power);
+                match op {
+                    BinaryOp::Range | BinaryOp::RangeInclusive => format!("{}{}{}", lhs, op.as_str(), rhs),
+                    _ => format!("{} {} {}", lhs, op.as_str(), rhs),
+                }
+            },
+        }
+    }
+
+    fn target(&self, target: &Target) -> String {
+        match target {
+            Target::Name { token } => token.literal.clone(),
+            Target::Index { target, index, .. } => format!("{}[{}]", self.postfix_target(target), self.expr(index)),
+            Target::Member { target, name, .. } => format!("{}.{}", self.postfix_target(target), name.literal),
+            Target::Tuple { token, items } => {
+                let parenthesized = parenthesized(token, items.first().map(Target::token));
+                let items: Vec<String> = items.iter().map(|item| self.target(item)).collect();
+                match parenthesized {
+                    true if items.len() == 1 => format!("({},)", items[0]),
+                    true => format!("({})", items.join(", ")),
+                    false => items.join(", "),
+                }
+            },
+        }
+    }
+
+    /// Parameters and body, the text after `func name`.
+    fn function(&self, function: &FuncDef) -> String {
+        let params: Vec<String> = function.params.iter().map(|param| match param {
+            Param::Required { name } => name.literal.clone(),
+            Param::Optional { name, default } => format!("{} = {}", name.literal, self.expr(default)),
+            Param::Variadic { name } => format!("...{}", name.literal),
+        }).collect();
+        let mut body = Printer { operators: self.operators.clone(), out: String::new(), depth: self.depth };
+        body.block(&function.body);
+        format!("({})\n{}", params.join(", "), body.out.trim_end_matches('\n'))
+    }
+
+    /// Nested blocks in `text` are indented already, only its first line
+    /// is indented here.
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn block(&mut self, body: &[Stmt]) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str("{\n");
+        self.depth += 1;
+        for stmt in body {
+            self.stmt(stmt);
+        }
+        self.depth -= 1;
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str("}\n");
+    }
+
+    fn label(label: &Option<Token>) -> String {
+        match label {
+            Some(label) => format!("{}: ", label.literal),
+            None => String::new(),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let text = match stmt {
+            Stmt::Print { value, .. } => format!("print {}", self.expr(value)),
+            Stmt::Assign { target, value, .. } => format!("{} = {}", self.target(target), self.expr(value)),
+            Stmt::While { label, condition, body, .. } => {
+                self.line(&format!("{}while {}", Printer::label(label), self.expr(condition)));
+                return self.block(body);
+            },
+            Stmt::For { label, target, iterable, body, .. } => {
+                self.line(&format!("{}for {} in {}", Printer::label(label), self.target(target), self.expr(iterable)));
+                return self.block(body);
+            },
+            Stmt::Break { label: Some(label), .. } => format!("break {}", label.literal),
+            Stmt::Break { label: None, .. } => "break".to_string(),
+            Stmt::Continue { label: Some(label), .. } => format!("continue {}", label.literal),
+            Stmt::Continue { label: None, .. } => "continue".to_string(),
+            Stmt::Return { value: Some(value), .. } => format!("return {}", self.expr(value)),
+            Stmt::Return { value: None, .. } => "return".to_string(),
+            Stmt::FuncDecl { name, function } => format!("func {}{}", name.literal, self.function(function)),
+            Stmt::OperatorDecl { op, precedence, function } =>
+                format!("operator infix {} precedence {} {}", op.literal, precedence, self.function(function)),
+            Stmt::Expression { value } => self.expr(value),
+        };
+        self.line(&text);
+    }
+}
+
+fn builtin_operators() -> HashMap<String, (i32, Assoc)> {
+    INFIX_OPERATORS.iter()
+        .map(|(op, binding_power, assoc)| (op.to_string(), (*binding_power, *assoc)))
+        .collect()
+}
+
+/// Prints an expression. User defined operators in it are parenthesized as
+/// if they had the lowest precedence.
+pub fn expression(expr: &Expr) -> String {
+    let printer = Printer { operators: builtin_operators(), out: String::new(), depth: 0 };
+    printer.expr(expr)
+}
+
+/// Prints a program so that parsing the result gives back an equal AST.
+pub fn program(program: &[Stmt]) -> String {
+    let mut operators = builtin_operators();
+    for stmt in program {
+        if let Stmt::OperatorDecl { op, precedence, .. } = stmt {
+            operators.insert(op.literal.clone(), (*precedence, Assoc::Left));
+        }
+    }
+    let mut printer = Printer { operators, out: String::new(), depth: 0 };
+    for stmt in program {
+        printer.stmt(stmt);
+    }
+    printer.out
+}
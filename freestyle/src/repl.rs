@@ -0,0 +1,214 @@
+use super::ast;
+use super::builtin;
+use super::error::Error;
+use super::interpreter::Interpreter;
+use super::lineedit::{Input, LineEditor};
+use super::parser::Parser;
+use super::printer;
+use super::read_source;
+use super::tokenizer::{self, TokenType, Tokenizer};
+use super::value::Value;
+
+const HELP: &str = "\
+Enter statements or expressions, the value of an expression is printed.
+Input continues on the next line while brackets are open.
+  :help         show this help
+  :vars         list global variables
+  :funcs        list functions and operators
+  :load <file>  run a file in the current session
+  :reset        forget all variables, functions and operators
+  :quit         leave, as does end of input (Ctrl-D)
+Tab completes keywords and names, Up and Down recall earlier lines.
+Ctrl-C discards the input being typed, while code runs it ends the session.";
+
+const COMMANDS: [&str; 6] = [":help", ":vars", ":funcs", ":load", ":reset", ":quit"];
+
+/// Interactive session. The interpreter keeps its globals between inputs.
+pub struct Repl {
+    interpreter: Interpreter,
+    max_call_depth: usize,
+    /// Operators declared so far with their precedence, every input is
+    /// parsed by a new parser which has to know them.
+    operators: Vec<(String, i32)>,
+}
+
+fn signature(function: &ast::FuncDef) -> String {
+    let params: Vec<String> = function.params.iter().map(|param| match param {
+        ast::Param::Required { name } => name.literal.clone(),
+        ast::Param::Optional { name, default } => format!("{} = {}", name.literal, printer::expression(default)),
+        ast::Param::Variadic { name } => format!("...{}", name.literal),
+    }).collect();
+    format!("({})", params.join(", "))
+}
+
+impl Repl {
+    pub fn new(max_call_depth: usize) -> Repl {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_call_depth(max_call_depth);
+        Repl {
+            interpreter,
+            max_call_depth,
+            operators: Vec::new(),
+        }
+    }
+
+    fn tokenizer(&self, source: &str) -> Tokenizer {
+        let mut tokenizer = Tokenizer::new(source);
+        for (op, _) in &self.operators {
+            tokenizer.add_operator(op);
+        }
+        tokenizer
+    }
+
+    fn parser(&self, source: &str) -> Parser {
+        let mut parser = Parser::new(Tokenizer::new(source));
+        for (op, precedence) in &self.operators {
+            parser.declare_operator(op, *precedence);
+        }
+        parser
+    }
+
+    /// Brackets left open at the end of `source`, 0 when it does not
+    /// tokenize, the error is reported when it is run.
+    fn open_brackets(&self, source: &str) -> i32 {
+        match self.tokenizer(source).tokens() {
+            Ok(tokens) => tokens.iter().map(|token| match token.token_type {
+                TokenType::LP | TokenType::LSquare | TokenType::LBraceket => 1,
+                TokenType::RP | TokenType::RSquare | TokenType::RBraceket => -1,
+                _ => 0,
+            }).sum(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Whether the syntax error of `source` is at its end, e.g. after
+    /// `func f()` whose block starts on the next line.
+    fn ends_early(&self, source: &str) -> bool {
+        let eof = match self.tokenizer(source).tokens() {
+            Ok(tokens) => tokens.last().cloned(),
+            Err(_) => None,
+        };
+        match (eof, self.parser(source).parse()) {
+            (Some(eof), Err(msg)) => msg.starts_with(&format!("line:{}, column:{},", eof.row, eof.col)),
+            _ => false,
+        }
+    }
+
+    /// Runs one input, returns the value of a bare expression.
+    fn eval(&mut self, source: &str) -> Result<Option<Value>, Error> {
+        if let Ok(expr) = self.parser(source).parse_expression() {
+            return self.interpreter.evaluate(&expr).map(Some);
+        }
+        let program = self.parser(source).parse()?;
+        // Operators are declared before any statement runs, they stay
+        // declared when one of the statements fails.
+        for stmt in &program {
+            if let ast::Stmt::OperatorDecl { op, precedence, .. } = stmt {
+                self.operators.push((op.literal.clone(), *precedence));
+            }
+        }
+        self.interpreter.execute(&program)?;
+        Ok(None)
+    }
+
+    fn list_vars(&self) {
+        for variable in self.interpreter.globals() {
+            match variable.value {
+                Value::Function(_) | Value::Builtin(_) => (),
+                value => println!("{} = {}", variable.name, value.repr()),
+            }
+        }
+    }
+
+    fn list_funcs(&self) {
+        for variable in self.interpreter.globals() {
+            if let Value::Function(function) = variable.value {
+                println!("func {}{}", variable.name, signature(&function.def));
+            }
+        }
+        for (op, function) in self.interpreter.operators() {
+            let precedence = self.operators.iter().find(|(name, _)| *name == op).map_or(0, |(_, p)| *p);
+            if let Value::Function(function) = function {
+                println!("operator infix {} precedence {} {}", op, precedence, signature(&function.def));
+            }
+        }
+    }
+
+    /// Words `word` may be completed to.
+    fn completions(&self, word: &str) -> Vec<String> {
+        if word.starts_with(':') {
+            return COMMANDS.iter().map(|command| command.to_string()).collect();
+        }
+        let mut words: Vec<String> = tokenizer::keywords().to_vec();
+        words.extend(builtin::names().iter().map(|name| name.to_string()));
+        words.extend(self.interpreter.globals().into_iter().map(|variable| variable.name));
+        words
+    }
+
+    fn load(&mut self, filename: &str) -> Result<(), Error> {
+        let contents = read_source(filename)?;
+        self.eval(&contents).map(|_| ())
+    }
+
+    /// Returns false when the session ends.
+    fn command(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        match (words.next().unwrap_or(""), words.next()) {
+            (":help", None) => println!("{}", HELP),
+            (":vars", None) => self.list_vars(),
+            (":funcs", None) => self.list_funcs(),
+            (":load", Some(filename)) => {
+                if let Err(err) = self.load(filename) {
+                    eprintln!("{}", err);
+                }
+            },
+            (":reset", None) => *self = Repl::new(self.max_call_depth),
+            (":quit", None) => return false,
+            _ => eprintln!("unknown command {}, type :help for help", line.trim()),
+        }
+        true
+    }
+
+    pub fn run(&mut self, quiet: bool) {
+        if !quiet {
+            println!("freestyle {}, type :help for help", env!("CARGO_PKG_VERSION"));
+        }
+        let mut editor = LineEditor::new();
+        let mut source = String::new();
+        loop {
+            let prompt = if source.is_empty() { ">>> " } else { "... " };
+            let line = match editor.read_line(prompt, &|word| self.completions(word)) {
+                Input::Line(line) => line,
+                Input::Interrupted => {
+                    source.clear();
+                    continue;
+                },
+                Input::End => break,
+            };
+            if source.is_empty() && line.trim_start().starts_with(':') {
+                if !self.command(&line) {
+                    return;
+                }
+                continue;
+            }
+            source.push_str(&line);
+            source.push('\n');
+            if source.trim().is_empty() {
+                source.clear();
+                continue;
+            }
+            // A blank line ends input that only waits for a block, so that
+            // its syntax error is shown.
+            if self.open_brackets(&source) > 0 || (!line.trim().is_empty() && self.ends_early(&source)) {
+                continue;
+            }
+            match self.eval(&source) {
+                Ok(Some(Value::Nil)) | Ok(None) => (),
+                Ok(Some(value)) => println!("{}", value.repr()),
+                Err(err) => eprintln!("{}", err),
+            }
+            source.clear();
+        }
+        println!();
+    }
+}
@@ -0,0 +1,5 @@
+//! Token types, node types and the table driven parser that `build.rs`
+//! generates from `grammar.txt`. The parser builds a concrete syntax tree,
+//! `parser::Parser` turns it into the AST.
+#![allow(dead_code)]
+include!(concat!(env!("OUT_DIR"), "/grammar.rs"));
@@ -1,391 +1,415 @@
-use std::vec;
-use std::collections::VecDeque;
-use std::collections::HashMap;
-use lazy_static;
-
-pub struct Tokenizer {
-    current_pos: usize,
-    chars: vec::Vec<char>,
-    tokens: VecDeque<Token>,
-    current_line: u32,
-    current_column: u32,
-}
-
-#[derive(Copy, Clone)]
-pub enum TokenType {
-    Assign,
-    Add,
-    Sub,
-    Mul,
-    Div,
-    Mod,
-    Pow,
-    Symbol,
-    While,
-    LP,
-    RP,
-    LBraceket,
-    RBraceket,
-    Integer,
-    FuncDecl,
-    Return,
-    Comma,
-    Print,
-    Newline,
-    EOF,
-}
-lazy_static! {
-    static ref keywrods: HashMap<String, TokenType> = {
-        let mut map = HashMap::new();
-        map.insert("while".to_string(), TokenType::While);
-        map.insert("func".to_string(), TokenType::FuncDecl);
-        map.insert("return".to_string(), TokenType::Return);
-        map.insert("print".to_string(), TokenType::Print);
-        map
-    };
-}
-
-pub struct Token {
-    pub token_type: TokenType,
-    pub literal: String,
-    pub row: u32,
-    pub col: u32,
-}
-
-impl Token {
-    pub fn new() -> Token {
-        Token {
-            token_type: TokenType::EOF,
-            literal: "".to_string(),
-            row: 0,
-            col: 0,
-        }
-    }
-}
-
-impl Clone for Token {
-    fn clone(&self) -> Token {
-        Token {
-            token_type: self.token_type,
-            literal: self.literal.clone(),
-            row: self.row,
-            col: self.col,
-        }
-    }
-}
-
-impl Tokenizer {
-
-    fn skip_whitespace(&mut self) {
-        loop {
-            if self.chars.len() > self.current_pos &&
-                self.chars[self.current_pos].is_whitespace() &&
-                self.chars[self.current_pos] != '\n' {
-                self.current_pos += 1;
-                self.current_column += 1;
-                continue;
-            }
-            return;
-        }
-    }
-
-    fn eof(&self) -> Result<Token, String> {
-        Ok(Token {
-            token_type: TokenType::EOF,
-            literal: String::from(""),
-            row: self.current_line,
-            col: self.current_column,
-        })
-    }
-
-    //TODO: support _ and digit in name
-    //FIXME: if the last line like 'print a' without <newline>, it will crash because of OUT OF INDEX
-    fn symbol_or_keyword(&mut self) -> Result<Token, String> {
-        let mut string = String::from("");
-        let old_column = self.current_column;
-        loop {
-            let c = self.chars[self.current_pos];
-            if c.is_alphabetic() {
-                string.push(c);
-                self.current_pos += 1;
-                self.current_column += 1;
-                continue;
-            }
-            if c.is_whitespace() {
-                //keyword
-                self.current_pos += 1;
-                match keywrods.get(&string) {
-                    Some(token_type) => return Ok(Token {
-                        literal: string,
-                        token_type: token_type.clone(),
-                        row: self.current_line,
-                        col: old_column,
-                    }),
-                    None => (),
-                }
-                let symbol =  Ok(Token {
-                    literal: string.clone(),
-                    token_type: TokenType::Symbol,
-                    row: self.current_line,
-                    col: old_column,
-                });
-                if c == '\n' {
-                    self.current_line += 1;
-                    self.current_pos = 1;
-                } else {
-                    self.current_column += 1;
-                }
-                return symbol;
-            }
-            return Err(format!("unexpected character {}", c).to_string());
-        }
-    }
-
-    fn integer(&mut self) -> Result<Token, String> {
-        let mut string = String::from("");
-        let old_column = self.current_column;
-        loop {
-            let c = self.chars[self.current_pos];
-            if c.is_digit(10) {
-                string.push(c);
-                self.current_pos += 1;
-                self.current_column += 1;
-                continue;
-            }
-            if c.is_whitespace() || c.is_ascii_punctuation() {
-                self.current_pos += 1;
-                let result = Ok(Token {
-                    literal: string.clone(),
-                    token_type: TokenType::Integer,
-                    row: self.current_line,
-                    col: old_column,
-                });
-                if c == '\n' {
-                    self.current_line += 1;
-                    self.current_column = 1;
-                } else {
-                    self.current_column += 1;
-                }
-                return result;
-            }
-            return Err(format!("unexpected character {}", c).to_string());
-        }
-    }
-
-    fn assign(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::Assign,
-            literal: "=".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn add(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::Add,
-            literal: "+".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn sub(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::Sub,
-            literal: "-".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn mul(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::Mul,
-            literal: "*".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn div(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::Div,
-            literal: "/".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn pow(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::Pow,
-            literal: "^".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn remainder(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::Mod,
-            literal: "%".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn left_parenthese(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::LP,
-            literal: "(".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn right_parenthese(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::RP,
-            literal: ")".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn left_bracket(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::LBraceket,
-            literal: "{".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn right_bracket(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::RBraceket,
-            literal: "}".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn comma(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        self.current_column += 1;
-        Ok(Token {
-            token_type: TokenType::Comma,
-            literal: ",".to_string(),
-            row: self.current_line,
-            col: self.current_column - 1,
-        })
-    }
-
-    fn new_line(&mut self) -> Result<Token, String> {
-        self.current_pos += 1;
-        let result = Ok(Token {
-            token_type: TokenType::Newline,
-            literal: "\n".to_string(),
-            row: self.current_line,
-            col: self.current_column,
-        });
-        self.current_line += 1;
-        self.current_column = 1;
-        return result;
-    }
-    fn next(&mut self) -> Result<Token, String> {
-        if self.current_pos == self.chars.len() {
-            return self.eof();
-        }
-        self.skip_whitespace();
-        let next_char = self.chars[self.current_pos];
-        if next_char.is_alphabetic() || next_char == '_' {
-            return self.symbol_or_keyword();
-        }
-        if next_char.is_digit(10) {
-            return self.integer();
-        }
-        match next_char {
-            '=' => return self.assign(),
-            '\n' => return self.new_line(),
-            '+' => return self.add(),
-            '-' => return self.sub(),
-            '*' => return self.mul(),
-            '/' => return self.div(),
-            '%' => return self.remainder(),
-            '^' => return self.pow(),
-            '(' => return self.left_parenthese(),
-            ')' => return self.right_parenthese(),
-            '{' => return self.left_bracket(),
-            '}' => return self.right_bracket(),
-            ',' => return self.comma(),
-            _ => return Err(format!("unexpected characters {}", next_char).to_string()),
-        }
-    }
-
-    pub fn look_ahead(&mut self, n: usize) -> Result<Token, String> {
-        assert_ne!(n, 0);
-        let mut has_read = self.tokens.len();
-        while has_read < n {
-            let next_token = match self.next() {
-                Ok(token) => token,
-                Err(msg) => return Err(msg),
-            };
-            self.tokens.push_back(next_token.clone());
-            has_read = self.tokens.len();
-            match next_token.token_type {
-                TokenType::EOF => return match self.tokens.back() {
-                    Some(token) => Ok(token.clone()),
-                    None => panic!(format!("line:{}, column:{}, fatal error, it should be a TokenType::EOF here",
-                        next_token.row, next_token.col)),
-                },
-                _ => (),
-            }
-        }
-        return Ok(self.tokens[n-1].clone());
-    }
-
-    pub fn eat(&mut self, n: usize) {
-        assert_ne!(n, 0);
-        for _i in 0..n {
-            self.tokens.pop_front();
-        }
-    }
-
-    pub fn new(s: &String) -> Tokenizer {
-        let mut t = Tokenizer {
-            current_pos: 0,
-            chars: vec::Vec::<char>::new(),
-            tokens: VecDeque::<Token>::new(),
-            current_column: 1,
-            current_line: 1,
-        };
-        for c in s.chars() {
-            t.chars.push(c);
-        }
-        t
-    }
-}
+use std::vec;
+use std::collections::VecDeque;
+use super::grammar::{Grammar, TokenDef, plain_pattern};
+use super::lexgen::{Lexer, Pattern};
+
+pub struct Tokenizer {
+    current_pos: usize,
+    chars: vec::Vec<char>,
+    tokens: VecDeque<Token>,
+    current_line: u32,
+    current_column: u32,
+    /// Spellings of user defined operators, longest first.
+    operators: Vec<String>,
+    /// Whether `#` comments are returned as tokens instead of skipped.
+    comments: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TokenType {
+    Assign,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    DotDot,
+    DotDotEq,
+    Ellipsis,
+    Dot,
+    And,
+    Or,
+    Not,
+    CustomOp,
+    Symbol,
+    While,
+    For,
+    In,
+    LP,
+    RP,
+    LBraceket,
+    RBraceket,
+    LSquare,
+    RSquare,
+    Colon,
+    Integer,
+    Str,
+    FuncDecl,
+    Return,
+    Operator,
+    Break,
+    Continue,
+    Comma,
+    Print,
+    Newline,
+    Comment,
+    Eof,
+}
+lazy_static! {
+    /// The token definitions of `grammar.txt` compiled to a DFA, with the
+    /// `TokenType` of every token, `None` for the skipped ones.
+    static ref LEXER: (Lexer, Vec<Option<TokenType>>) = {
+        let grammar = Grammar::parse(include_str!("../grammar.txt")).expect("grammar.txt is invalid");
+        // User defined operators are matched against the declared ones only.
+        let tokens: Vec<&TokenDef> = grammar.tokens[..grammar.eof()].iter()
+            .filter(|token| grammar_token(&token.name) != Some(TokenType::CustomOp))
+            .collect();
+        let patterns: Vec<Pattern> = tokens.iter().map(|token| match plain_pattern(&token.pattern) {
+            _ if token.literal => Pattern::Literal(token.pattern.clone()),
+            Some(text) => Pattern::Literal(text),
+            None => Pattern::Regex(token.pattern.clone()),
+        }).collect();
+        let lexer = Lexer::new(&patterns).expect("grammar.txt has an invalid token pattern");
+        (lexer, tokens.iter().map(|token| grammar_token(&token.name)).collect())
+    };
+}
+
+lazy_static! {
+    /// `CUSTOM_OPERATOR` of `grammar.txt`, the spellings operators may be
+    /// declared with.
+    static ref OPERATOR_LEXER: Lexer = {
+        let grammar = Grammar::parse(include_str!("../grammar.txt")).expect("grammar.txt is invalid");
+        let token = grammar.tokens.iter().find(|token| token.name == "CUSTOM_OPERATOR")
+            .expect("grammar.txt has no CUSTOM_OPERATOR");
+        Lexer::new(&[Pattern::Regex(token.pattern.clone())]).expect("grammar.txt has an invalid token pattern")
+    };
+}
+
+lazy_static! {
+    /// The literal tokens of `grammar.txt`: keywords such as `while` and
+    /// symbols such as `..=`, each list sorted.
+    static ref LITERALS: (Vec<String>, Vec<String>) = {
+        let grammar = Grammar::parse(include_str!("../grammar.txt")).expect("grammar.txt is invalid");
+        let mut literals: Vec<String> = grammar.tokens[..grammar.eof()].iter()
+            .filter_map(|token| match plain_pattern(&token.pattern) {
+                _ if token.literal => Some(token.pattern.clone()),
+                plain => plain,
+            })
+            .collect();
+        literals.sort();
+        literals.into_iter().partition(|text| text.chars().all(|c| c.is_alphabetic()))
+    };
+}
+
+pub fn keywords() -> &'static [String] {
+    &LITERALS.0
+}
+
+/// The built-in token a user defined operator spelled `op` would clash
+/// with: one spelled the same or starting with `op`, such as `!=` for `!`.
+fn builtin_clash(op: &str) -> Option<&'static str> {
+    LITERALS.1.iter().find(|symbol| symbol.starts_with(op)).map(|symbol| symbol.as_str())
+}
+
+/// `TokenType` of a token named in `grammar.txt`.
+pub fn grammar_token(name: &str) -> Option<TokenType> {
+    let token_type = match name {
+        "WHITESPACE" => return None,
+        "INTEGER" => TokenType::Integer,
+        "NAME" => TokenType::Symbol,
+        "STRING" => TokenType::Str,
+        "NEWLINE" => TokenType::Newline,
+        "COMMENT" => TokenType::Comment,
+        "EQUAL" => TokenType::Assign,
+        "PLUS" => TokenType::Add,
+        "MINUS" => TokenType::Sub,
+        "STAR" => TokenType::Mul,
+        "SLASH" => TokenType::Div,
+        "PERCENT" => TokenType::Mod,
+        "CARET" => TokenType::Pow,
+        "LESS" => TokenType::Lt,
+        "GREATER" => TokenType::Gt,
+        "LESS_EQUAL" => TokenType::Le,
+        "GREATER_EQUAL" => TokenType::Ge,
+        "EQUAL_EQUAL" => TokenType::Eq,
+        "BANG_EQUAL" => TokenType::Ne,
+        "DOT_DOT" => TokenType::DotDot,
+        "DOT_DOT_EQUAL" => TokenType::DotDotEq,
+        "DOT_DOT_DOT" => TokenType::Ellipsis,
+        "DOT" => TokenType::Dot,
+        "AND" => TokenType::And,
+        "OR" => TokenType::Or,
+        "NOT" => TokenType::Not,
+        "WHILE" => TokenType::While,
+        "FOR" => TokenType::For,
+        "IN" => TokenType::In,
+        "L_PAREN" => TokenType::LP,
+        "R_PAREN" => TokenType::RP,
+        "L_BRACE" => TokenType::LBraceket,
+        "R_BRACE" => TokenType::RBraceket,
+        "L_SQUARE" => TokenType::LSquare,
+        "R_SQUARE" => TokenType::RSquare,
+        "COLON" => TokenType::Colon,
+        "COMMA" => TokenType::Comma,
+        "FUNC" => TokenType::FuncDecl,
+        "RETURN" => TokenType::Return,
+        "OPERATOR" => TokenType::Operator,
+        "CUSTOM_OPERATOR" => TokenType::CustomOp,
+        "BREAK" => TokenType::Break,
+        "CONTINUE" => TokenType::Continue,
+        "PRINT" => TokenType::Print,
+        "EOF" => TokenType::Eof,
+        _ => panic!("token {} of grammar.txt has no TokenType", name),
+    };
+    Some(token_type)
+}
+
+pub struct Token {
+    pub token_type: TokenType,
+    pub literal: String,
+    pub row: u32,
+    pub col: u32,
+}
+
+impl Clone for Token {
+    fn clone(&self) -> Token {
+        Token {
+            token_type: self.token_type,
+            literal: self.literal.clone(),
+            row: self.row,
+            col: self.col,
+        }
+    }
+}
+
+/// Tokens compare by type and literal, positions are ignored so the ASTs of
+/// the same code formatted differently are equal.
+impl PartialEq for Token {
+    fn eq(&self, other: &Token) -> bool {
+        self.token_type == other.token_type && self.literal == other.literal
+    }
+}
+
+impl Tokenizer {
+
+    /// Moves past `len` characters.
+    fn advance(&mut self, len: usize) {
+        for _ in 0..len {
+            if self.chars[self.current_pos] == '\n' {
+                self.current_line += 1;
+                self.current_column = 1;
+            } else {
+                self.current_column += 1;
+            }
+            self.current_pos += 1;
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some((index, len)) = LEXER.0.longest_match(&self.chars[self.current_pos..]) {
+            match LEXER.1[index] {
+                Some(TokenType::Comment) if !self.comments => (),
+                Some(_) => return,
+                None => (),
+            }
+            self.advance(len);
+        }
+    }
+
+    fn eof(&self) -> Result<Token, String> {
+        Ok(Token {
+            token_type: TokenType::Eof,
+            literal: String::from(""),
+            row: self.current_line,
+            col: self.current_column,
+        })
+    }
+
+    /// The value of a string literal, `token.literal` still has its quotes.
+    fn unescape(token: &Token) -> Result<String, String> {
+        let chars: Vec<char> = token.literal.chars().collect();
+        let mut string = String::new();
+        let mut i = 1;
+        while i < chars.len() - 1 {
+            if chars[i] != '\\' {
+                string.push(chars[i]);
+                i += 1;
+                continue;
+            }
+            string.push(match chars[i + 1] {
+                'n' => '\n',
+                't' => '\t',
+                '"' => '"',
+                '\\' => '\\',
+                escaped => return Err(format!("line:{}, column:{}, unknown escape sequence \\{}",
+                    token.row, token.col + i as u32, escaped)),
+            });
+            i += 2;
+        }
+        Ok(string)
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.current_pos + offset).copied()
+    }
+
+    /// Multi-character operators such as `==`, `<=` and `..=`.
+    fn operator(&mut self, token_type: TokenType, literal: &str) -> Result<Token, String> {
+        let len = literal.chars().count();
+        self.current_pos += len;
+        self.current_column += len as u32;
+        Ok(Token {
+            token_type,
+            literal: literal.to_string(),
+            row: self.current_line,
+            col: self.current_column - len as u32,
+        })
+    }
+
+    /// Length of the longest user defined operator at the current position.
+    fn user_operator(&self) -> Option<usize> {
+        self.operators.iter()
+            .find(|op| op.chars().enumerate().all(|(i, c)| self.peek(i) == Some(c)))
+            .map(|op| op.chars().count())
+    }
+
+    /// Reads the spelling of an operator being declared. Must be called with
+    /// no tokens looked ahead, the spelling may not lex as a single token yet.
+    pub fn custom_operator(&mut self) -> Result<Token, String> {
+        assert!(self.tokens.is_empty());
+        self.skip_whitespace();
+        let literal: String = match OPERATOR_LEXER.longest_match(&self.chars[self.current_pos..]) {
+            Some((_, len)) => self.chars[self.current_pos..self.current_pos + len].iter().collect(),
+            None => return Err(format!("line:{}, column:{}, syntax error, expect operator",
+                self.current_line, self.current_column)),
+        };
+        if let Some(builtin) = builtin_clash(&literal) {
+            return Err(format!("line:{}, column:{}, syntax error, operator '{}' clashes with the built-in '{}'",
+                self.current_line, self.current_column, literal, builtin));
+        }
+        self.operator(TokenType::CustomOp, &literal)
+    }
+
+    pub fn add_operator(&mut self, op: &str) {
+        self.operators.push(op.to_string());
+        self.operators.sort_by_key(|op| std::cmp::Reverse(op.chars().count()));
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        self.skip_whitespace();
+        if self.current_pos == self.chars.len() {
+            return self.eof();
+        }
+        // User defined operators take part in the longest match, a built-in
+        // token of the same length can't exist.
+        let builtin = LEXER.0.longest_match(&self.chars[self.current_pos..]);
+        match (self.user_operator(), builtin) {
+            (Some(len), Some((_, builtin_len))) if len < builtin_len => (),
+            (Some(len), _) => {
+                let literal: String = self.chars[self.current_pos..self.current_pos + len].iter().collect();
+                return self.operator(TokenType::CustomOp, &literal);
+            },
+            (None, _) => (),
+        }
+        let (index, len) = match builtin {
+            Some(found) => found,
+            None if self.chars[self.current_pos] == '"' => return Err(format!(
+                "line:{}, column:{}, unterminated string literal", self.current_line, self.current_column)),
+            None => return Err(format!("line:{}, column:{}, unexpected character {}",
+                self.current_line, self.current_column, self.chars[self.current_pos])),
+        };
+        let mut token = Token {
+            token_type: LEXER.1[index].unwrap(),
+            literal: self.chars[self.current_pos..self.current_pos + len].iter().collect(),
+            row: self.current_line,
+            col: self.current_column,
+        };
+        self.advance(len);
+        match token.token_type {
+            TokenType::Str => token.literal = Tokenizer::unescape(&token)?,
+            TokenType::Integer => match self.peek(0) {
+                Some(c) if c.is_alphabetic() || c == '_' => return Err(format!("line:{}, column:{}, unexpected character {}",
+                    self.current_line, self.current_column, c)),
+                _ => (),
+            },
+            _ => (),
+        }
+        Ok(token)
+    }
+
+    pub fn look_ahead(&mut self, n: usize) -> Result<Token, String> {
+        assert_ne!(n, 0);
+        let mut has_read = self.tokens.len();
+        while has_read < n {
+            let next_token = self.next()?;
+            self.tokens.push_back(next_token.clone());
+            has_read = self.tokens.len();
+            if next_token.token_type == TokenType::Eof {
+                return match self.tokens.back() {
+                    Some(token) => Ok(token.clone()),
+                    None => panic!("line:{}, column:{}, fatal error, it should be a TokenType::Eof here",
+                        next_token.row, next_token.col),
+                };
+            }
+        }
+        Ok(self.tokens[n-1].clone())
+    }
+
+    pub fn eat(&mut self, n: usize) {
+        assert_ne!(n, 0);
+        for _i in 0..n {
+            self.tokens.pop_front();
+        }
+    }
+
+    /// Returns `#` comments as `TokenType::Comment` tokens from now on.
+    pub fn keep_comments(&mut self) {
+        self.comments = true;
+    }
+
+    /// Reads every remaining token up to and including `Eof`. Operators
+    /// declared with `operator infix` are registered on the way, as the
+    /// parser would.
+    pub fn tokens(&mut self) -> Result<Vec<Token>, String> {
+        let mut tokens = Vec::<Token>::new();
+        loop {
+            let token = self.look_ahead(1)?;
+            self.eat(1);
+            let declares_operator = token.literal == "infix" && matches!(tokens.last(),
+                Some(previous) if previous.token_type == TokenType::Operator);
+            let at_eof = token.token_type == TokenType::Eof;
+            tokens.push(token);
+            if declares_operator {
+                let op = self.custom_operator()?;
+                self.add_operator(&op.literal);
+                tokens.push(op);
+            }
+            if at_eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    pub fn new(s: &str) -> Tokenizer {
+        let mut t = Tokenizer {
+            current_pos: 0,
+            chars: vec::Vec::<char>::new(),
+            tokens: VecDeque::<Token>::new(),
+            current_column: 1,
+            current_line: 1,
+            operators: Vec::new(),
+            comments: false,
+        };
+        for c in s.chars() {
+            t.chars.push(c);
+        }
+        t
+    }
+}
@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use super::ast;
+use super::ntable::Env;
+
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Str(String),
+    List(Rc<RefCell<Vec<Value>>>),
+    Tuple(Rc<Vec<Value>>),
+    Map(Rc<RefCell<BTreeMap<Key, Value>>>),
+    /// Half-open integer range, `a..=b` is stored as `a..b+1`.
+    Range(i64, i64),
+    Function(Rc<Function>),
+    Builtin(&'static str),
+}
+
+/// A user defined function, `def` is shared with the AST it was declared in
+/// and `env` is the scope it was created in.
+pub struct Function {
+    pub name: String,
+    pub def: Rc<ast::FuncDef>,
+    pub env: Env,
+}
+
+/// The subset of values that may be used as map keys.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Bool(bool),
+    Integer(i64),
+    Str(String),
+    Tuple(Vec<Key>),
+}
+
+impl Value {
+    pub fn new_list(items: Vec<Value>) -> Value {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    pub fn new_tuple(items: Vec<Value>) -> Value {
+        Value::Tuple(Rc::new(items))
+    }
+
+    pub fn new_function(name: &str, def: &Rc<ast::FuncDef>, env: &Env) -> Value {
+        Value::Function(Rc::new(Function {
+            name: name.to_string(),
+            def: def.clone(),
+            env: env.clone(),
+        }))
+    }
+
+    pub fn new_map(entries: BTreeMap<Key, Value>) -> Value {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Integer(_) => "int",
+            Value::Str(_) => "str",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Map(_) => "map",
+            Value::Range(..) => "range",
+            Value::Function(_) | Value::Builtin(_) => "function",
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Bool(b) => *b,
+            Value::Integer(i) => *i != 0,
+            Value::Str(s) => !s.is_empty(),
+            Value::List(list) => !list.borrow().is_empty(),
+            Value::Tuple(items) => !items.is_empty(),
+            Value::Map(map) => !map.borrow().is_empty(),
+            Value::Range(start, end) => start < end,
+            Value::Function(_) | Value::Builtin(_) => true,
+        }
+    }
+
+    /// Structural equality, containers are compared element by element.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Integer(l), Value::Integer(r)) => l == r,
+            (Value::Str(l), Value::Str(r)) => l == r,
+            (Value::List(l), Value::List(r)) => {
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| l.equals(r))
+            },
+            (Value::Tuple(l), Value::Tuple(r)) =>
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| l.equals(r)),
+            (Value::Map(l), Value::Map(r)) => {
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|((lk, lv), (rk, rv))| lk == rk && lv.equals(rv))
+            },
+            (Value::Range(ls, le), Value::Range(rs, re)) => ls == rs && le == re,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::Builtin(l), Value::Builtin(r)) => l == r,
+            _ => false,
+        }
+    }
+
+    /// Iteration protocol used by `for ... in`: ranges yield integers, lists
+    /// and tuples yield their items, maps yield their keys and strings yield
+    /// one-character strings. Containers are snapshotted when the loop starts.
+    pub fn iter(&self) -> Result<ValueIter, String> {
+        match self {
+            Value::Range(start, end) => Ok(ValueIter::Range(*start, *end)),
+            Value::List(list) => Ok(ValueIter::Items(list.borrow().clone().into_iter())),
+            Value::Tuple(items) => Ok(ValueIter::Items((**items).clone().into_iter())),
+            Value::Map(map) => Ok(ValueIter::Items(
+                map.borrow().keys().map(|k| k.to_value()).collect::<Vec<Value>>().into_iter())),
+            Value::Str(s) => Ok(ValueIter::Items(
+                s.chars().map(|c| Value::Str(c.to_string())).collect::<Vec<Value>>().into_iter())),
+            _ => Err(format!("'{}' is not iterable", self.type_name())),
+        }
+    }
+
+    pub fn to_key(&self) -> Result<Key, String> {
+        match self {
+            Value::Bool(b) => Ok(Key::Bool(*b)),
+            Value::Integer(i) => Ok(Key::Integer(*i)),
+            Value::Str(s) => Ok(Key::Str(s.clone())),
+            Value::Tuple(items) => {
+                let mut keys = Vec::<Key>::new();
+                for item in items.iter() {
+                    keys.push(item.to_key()?);
+                }
+                Ok(Key::Tuple(keys))
+            },
+            _ => Err(format!("unhashable type '{}' used as map key", self.type_name())),
+        }
+    }
+
+    /// Like `Display`, but strings are quoted. Used for elements of containers.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::Str(s) => format!("{:?}", s),
+            _ => self.to_string(),
+        }
+    }
+}
+
+pub enum ValueIter {
+    Range(i64, i64),
+    Items(std::vec::IntoIter<Value>),
+}
+
+impl Iterator for ValueIter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            ValueIter::Range(start, end) => {
+                if start >= end {
+                    return None;
+                }
+                *start += 1;
+                Some(Value::Integer(*start - 1))
+            },
+            ValueIter::Items(items) => items.next(),
+        }
+    }
+}
+
+impl Key {
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::Bool(b) => Value::Bool(*b),
+            Key::Integer(i) => Value::Integer(*i),
+            Key::Str(s) => Value::Str(s.clone()),
+            Key::Tuple(keys) => Value::new_tuple(keys.iter().map(|k| k.to_value()).collect()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::List(list) => {
+                let items: Vec<String> = list.borrow().iter().map(|v| v.repr()).collect();
+                write!(f, "[{}]", items.join(", "))
+            },
+            Value::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(|v| v.repr()).collect();
+                if items.len() == 1 {
+                    write!(f, "({},)", items[0])
+                } else {
+                    write!(f, "({})", items.join(", "))
+                }
+            },
+            Value::Map(map) => {
+                let entries: Vec<String> = map.borrow().iter()
+                    .map(|(k, v)| format!("{}: {}", k.to_value().repr(), v.repr()))
+                    .collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            },
+            Value::Range(start, end) => write!(f, "{}..{}", start, end),
+            Value::Function(function) => write!(f, "<func {}>", function.name),
+            Value::Builtin(name) => write!(f, "<builtin {}>", name),
+        }
+    }
+}
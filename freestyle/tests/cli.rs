@@ -0,0 +1,404 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn freestyle(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_freestyle"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start freestyle");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+/// Output of a program that is expected to succeed.
+fn run(source: &str) -> String {
+    let output = freestyle(&["-e", source], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    stdout(&output)
+}
+
+/// First line of the error of a program that is expected not to parse.
+fn syntax_error(source: &str) -> String {
+    let output = freestyle(&["check", "-q", "-e", source], "");
+    assert_eq!(output.status.code(), Some(2), "{:?} parsed", source);
+    stderr(&output).trim_start_matches("-e: ").lines().next().unwrap_or("").to_string()
+}
+
+#[test]
+fn success_exits_with_0() {
+    let output = freestyle(&["-e", "print 1 + 2"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn runtime_error_exits_with_1() {
+    let output = freestyle(&["run", "-e", "print 1 / 0"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("runtime error, division by zero"), "{}", stderr(&output));
+}
+
+#[test]
+fn syntax_error_exits_with_2() {
+    let output = freestyle(&["-e", "print 1\nx = ("], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("line:2,"), "{}", stderr(&output));
+    // Nothing runs before the whole program is parsed.
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn missing_file_exits_with_1() {
+    let output = freestyle(&["run", "no/such/file.fs"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("read no/such/file.fs failed"));
+}
+
+#[test]
+fn usage_error_exits_with_2() {
+    assert_eq!(freestyle(&["--no-such-option"], "").status.code(), Some(2));
+    assert_eq!(freestyle(&["ast", "--print-expressions", "-e", "x = 1"], "").status.code(), Some(2));
+    assert_eq!(freestyle(&["run", "a.fs", "b.fs"], "").status.code(), Some(2));
+}
+
+#[test]
+fn reads_the_program_from_stdin() {
+    let output = freestyle(&["-"], "x = 20\nprint x + 1\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "21\n");
+}
+
+#[test]
+fn runs_a_file() {
+    let path = std::env::temp_dir().join(format!("freestyle-cli-{}.fs", std::process::id()));
+    std::fs::write(&path, "print \"from file\"\n").unwrap();
+    let output = freestyle(&[path.to_str().unwrap()], "");
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "from file\n");
+}
+
+#[test]
+fn expression_statements_run_for_their_effects() {
+    let output = freestyle(&["-e", "func f(x)\n{\n    print x * 2\n}\nf(4)\n1 + 2"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "8\n");
+}
+
+#[test]
+fn print_expressions_prints_top_level_values_except_nil() {
+    let source = "func f()\n{\n    return\n}\n1 + 2\n\"s\"\n[1, \"a\"]\nf()\nx = 5";
+    let output = freestyle(&["run", "--print-expressions", "-e", source], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n\"s\"\n[1, \"a\"]\n");
+}
+
+#[test]
+fn check_reports_the_worst_error() {
+    let output = freestyle(&["check", "-e", "x = 1"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "-e: ok\n");
+    // A runtime error is not found by check.
+    assert_eq!(freestyle(&["check", "-e", "print 1 / 0"], "").status.code(), Some(0));
+    let output = freestyle(&["check", "-", "no/such/file.fs"], "x = )");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn quiet_prints_only_errors() {
+    let output = freestyle(&["-q", "check", "-e", "x = 1"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+    let output = freestyle(&["fmt", "--check", "-q", "-"], "x=1\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn fmt_formats_stdin_to_stdout() {
+    let output = freestyle(&["fmt", "-"], "x=1+2\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "x = 1 + 2\n");
+    assert_eq!(freestyle(&["fmt", "-"], "x = (\n").status.code(), Some(2));
+}
+
+#[test]
+fn tokens_and_ast_dump_inline_code() {
+    let output = freestyle(&["tokens", "-e", "a+1"], "");
+    assert_eq!(stdout(&output), "1:1\tSymbol\t\"a\"\n1:2\tAdd\t\"+\"\n1:3\tInteger\t\"1\"\n1:4\tEof\t\"\"\n");
+    let output = freestyle(&["ast", "-e", "f(1)"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("(program\n  (expression @1:1\n    (call"), "{}", stdout(&output));
+}
+
+#[test]
+fn divmod_overflow_is_a_runtime_error() {
+    let output = freestyle(&["-e", "print divmod(-9223372036854775807 - 1, -1)"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("runtime error, integer overflow"), "{}", stderr(&output));
+    assert_eq!(stdout(&freestyle(&["-e", "print divmod(-7, 2)"], "")), "(-3, -1)\n");
+}
+
+#[test]
+fn range_bounds_do_not_overflow() {
+    let output = freestyle(&["-e", "print 9223372036854775807..=9223372036854775807"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("runtime error, integer overflow"), "{}", stderr(&output));
+    let output = freestyle(&["-e", "print len(-9223372036854775807 - 1..9223372036854775807)\nprint len(3..=1)"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "9223372036854775807\n0\n");
+}
+
+#[test]
+fn functions_may_be_called_before_their_definition() {
+    let source = "print even(10)\nprint odd(7)\n\
+        func even(n)\n{\n    while n > 0\n    {\n        return odd(n - 1)\n    }\n    return 1\n}\n\
+        func odd(n)\n{\n    while n > 0\n    {\n        return even(n - 1)\n    }\n    return 0\n}";
+    let output = freestyle(&["-e", source], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "1\n1\n");
+}
+
+#[test]
+fn duplicate_function_is_a_syntax_error() {
+    let source = "print 1\nfunc f()\n{\n    return 1\n}\nfunc f()\n{\n    return 2\n}";
+    let output = freestyle(&["-e", source], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("line:6, column:6, syntax error, function 'f' is already defined at line:2"), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+    let output = freestyle(&["check", "-e", source], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("-e: line:6, column:6, syntax error"), "{}", stderr(&output));
+}
+
+#[test]
+fn integer_overflow_is_a_runtime_error() {
+    let max = "9223372036854775807";
+    for code in [format!("{} + 1", max), format!("-{} - 2", max), format!("{} * 2", max), "2 ^ 63".to_string(),
+        format!("(-{} - 1) / -1", max), format!("(-{} - 1) % -1", max)] {
+        let output = freestyle(&["-e", &format!("print {}", code)], "");
+        assert_eq!(output.status.code(), Some(1), "{}", code);
+        assert!(stderr(&output).contains("runtime error, integer overflow"), "{}: {}", code, stderr(&output));
+    }
+    let output = freestyle(&["-e", "print 2 ^ -1"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("runtime error, negative exponent"), "{}", stderr(&output));
+    let output = freestyle(&["-e", "print 2 ^ 62\nprint (-1) ^ 9999999999\nprint 7 % 0"], "");
+    assert_eq!(stdout(&output), "4611686018427387904\n-1\n");
+    assert!(stderr(&output).contains("runtime error, division by zero"), "{}", stderr(&output));
+}
+
+#[test]
+fn repl_keeps_operators_of_a_failed_load() {
+    let path = std::env::temp_dir().join(format!("freestyle-repl-{}.fs", std::process::id()));
+    std::fs::write(&path, "operator infix <+> precedence 60 (a, b)\n{\n    return a * 10 + b\n}\nprint 1 / 0\n").unwrap();
+    let input = format!(":load {}\n1 <+> 2\n:load no/such/file.fs\n", path.display());
+    let output = freestyle(&["repl", "-q"], &input);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains(">>> 12\n"), "{}", stdout(&output));
+    let stderr = stderr(&output);
+    assert!(stderr.starts_with("line:5, column:9, runtime error, division by zero\n"), "{}", stderr);
+    assert!(stderr.contains("read no/such/file.fs failed"), "{}", stderr);
+}
+
+#[test]
+fn repl_echoes_the_values_of_expressions() {
+    let input = "1 + 2\nx = [1, \"a\"]\nx\nfunc f(a)\n{\n    return a * 2\n}\nf(4)\nprint 5\n";
+    let output = freestyle(&["repl", "-q"], input);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), ">>> 3\n>>> >>> [1, \"a\"]\n>>> ... ... ... >>> 8\n>>> 5\n>>> \n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn repl_reports_errors_on_stderr_and_continues() {
+    let input = "1 / 0\n:load no/such/file.fs\n:nope\nx = )\n1 + 1\n";
+    let output = freestyle(&["repl", "-q"], input);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).ends_with(">>> 2\n>>> \n"), "{}", stdout(&output));
+    let stderr = stderr(&output);
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", stderr);
+    assert_eq!(lines[0], "line:1, column:3, runtime error, division by zero");
+    assert!(lines[1].starts_with("read no/such/file.fs failed"), "{}", stderr);
+    assert_eq!(lines[2], "unknown command :nope, type :help for help");
+    assert!(lines[3].starts_with("line:1, column:5, syntax error"), "{}", stderr);
+}
+
+#[test]
+fn unbounded_recursion_is_a_stack_overflow() {
+    let source = "func f(n)\n{\n    return f(n + 1)\n}\nprint f(0)";
+    let output = freestyle(&["run", "--max-call-depth", "50", "-e", source], "");
+    assert_eq!(output.status.code(), Some(1));
+    let expected = "line:3, column:12, runtime error, stack overflow at line 3, maximum call depth is 50\n\
+        stack trace (most recent call first):\n    in f, called at line:3, column:12\n    \
+        ... previous call repeated 48 more times\n    in f, called at line:5, column:7\n";
+    assert_eq!(stderr(&output), expected);
+}
+
+#[test]
+fn call_depth_is_bounded() {
+    let output = freestyle(&["run", "--max-call-depth", "1000000", "-e", "print 1"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("--max-call-depth needs a number from 1 to 100000"), "{}", stderr(&output));
+    assert_eq!(freestyle(&["run", "--max-call-depth", "0", "-e", "print 1"], "").status.code(), Some(2));
+}
+
+#[test]
+fn break_and_continue_affect_the_innermost_loop() {
+    let source = "for i in 0..3\n{\n    for j in 0..3\n    {\n        print i * 10 + j\n        break\n    }\n\
+        for j in 0..3\n    {\n        continue\n        print \"unreachable\"\n    }\n    print i\n}";
+    assert_eq!(run(source), "0\n0\n10\n1\n20\n2\n");
+    let source = "i = 0\nn = 0\nwhile i < 5\n{\n    i = i + 1\n    n = n + i\n    continue\n    n = 100\n}\nprint n";
+    assert_eq!(run(source), "15\n");
+}
+
+#[test]
+fn labeled_break_and_continue_leave_outer_loops() {
+    let source = "outer: for i in 0..3\n{\n    inner: for j in 0..3\n    {\n\
+        while j == 1\n        {\n            continue outer\n        }\n\
+        while i == 2\n        {\n            break outer\n        }\n\
+        print i * 10 + j\n    }\n    print \"unreachable\"\n}\nprint \"done\"";
+    assert_eq!(run(source), "0\n10\ndone\n");
+    let source = "n = 0\nloop: while 1\n{\n    for x in [1, 2, 3]\n    {\n        n = n + x\n\
+        while n > 10\n        {\n            break loop\n        }\n    }\n}\nprint n";
+    assert_eq!(run(source), "12\n");
+}
+
+#[test]
+fn break_and_continue_outside_of_loops_are_syntax_errors() {
+    assert_eq!(syntax_error("break"), "line:1, column:1, syntax error, 'break' outside of a loop");
+    assert_eq!(syntax_error("x = 1\ncontinue"), "line:2, column:1, syntax error, 'continue' outside of a loop");
+    // A function body is not part of the loop around its declaration.
+    assert_eq!(syntax_error("for i in 0..3\n{\n    func g()\n    {\n        continue\n    }\n}"),
+        "line:5, column:9, syntax error, 'continue' outside of a loop");
+    assert_eq!(syntax_error("for i in 0..1\n{\n    break inner\n}"),
+        "line:3, column:11, syntax error, unknown loop label 'inner'");
+    assert_eq!(syntax_error("a: for i in 0..1\n{\n    a: while 0\n    {\n    }\n}"),
+        "line:3, column:5, syntax error, label 'a' shadows an enclosing loop label");
+}
+
+#[test]
+fn return_leaves_nested_loops() {
+    let source = "func find(xs, target)\n{\n    for i in 0..len(xs)\n    {\n        for x in xs[i]\n        {\n\
+        while x == target\n            {\n                return i\n            }\n        }\n    }\n    return -1\n}\n\
+        print find([[1, 2], [3, 4]], 4)\nprint find([[1]], 5)";
+    assert_eq!(run(source), "1\n-1\n");
+}
+
+#[test]
+fn bare_return_and_end_of_body_yield_nil() {
+    let source = "func bare()\n{\n    return\n    print \"unreachable\"\n}\nfunc empty()\n{\n}\n\
+        func assign(n)\n{\n    n = n + 1\n}\nprint bare()\nprint empty()\nprint assign(1)";
+    assert_eq!(run(source), "nil\nnil\nnil\n");
+    assert_eq!(run("f = func ()\n{\n    return\n}\nprint f()"), "nil\n");
+}
+
+#[test]
+fn return_outside_of_a_function_is_a_syntax_error() {
+    assert_eq!(syntax_error("return 1"), "line:1, column:1, syntax error, 'return' outside of a function");
+    assert_eq!(syntax_error("for i in 0..3\n{\n    return\n}"), "line:3, column:5, syntax error, 'return' outside of a function");
+}
+
+#[test]
+fn runtime_errors_in_functions_print_a_stack_trace() {
+    let source = "func inner(x)\n{\n    return x / 0\n}\nfunc outer(x)\n{\n    y = inner(x + 1)\n    return y\n}\nprint 1\nprint outer(1)";
+    let output = freestyle(&["-e", source], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "1\n");
+    let expected = "line:3, column:14, runtime error, division by zero\n\
+        stack trace (most recent call first):\n    in inner, called at line:7, column:9\n    in outer, called at line:11, column:7\n";
+    assert_eq!(stderr(&output), expected);
+}
+
+#[test]
+fn stack_trace_names_function_values_and_lambdas() {
+    let source = "f = func (x) { return x[5] }\nfunc apply(g, v)\n{\n    return g(v)\n}\napply(f, [1])";
+    let output = freestyle(&["-e", source], "");
+    assert_eq!(output.status.code(), Some(1));
+    let expected = "line:1, column:24, runtime error, list index 5 out of range\n\
+        stack trace (most recent call first):\n    in anonymous, called at line:4, column:12\n    in apply, called at line:6, column:1\n";
+    assert_eq!(stderr(&output), expected);
+}
+
+#[test]
+fn top_level_errors_have_no_stack_trace() {
+    let output = freestyle(&["-e", "print 1 / 0"], "");
+    assert_eq!(stderr(&output), "line:1, column:9, runtime error, division by zero\n");
+}
+
+#[test]
+fn functions_are_values() {
+    let source = "func twice(f, x)\n{\n    return f(f(x))\n}\nfunc inc(x)\n{\n    return x + 1\n}\n\
+        print twice(inc, 1)\ng = inc\nprint g(9)\nprint inc\nprint [inc, g][1](0)\n\
+        print twice(func (x) { return x * x }, 3)";
+    assert_eq!(run(source), "3\n10\n<func inc>\n1\n81\n");
+}
+
+#[test]
+fn nested_functions_are_local_and_may_be_returned() {
+    let source = "func make()\n{\n    func helper(x)\n    {\n        return x * 3\n    }\n    return helper\n}\n\
+        h = make()\nprint h(2)\nprint make()(5)\nprint helper(1)";
+    let output = freestyle(&["-e", source], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "6\n15\n");
+    assert!(stderr(&output).starts_with("line:12, column:7, runtime error, variable 'helper' not found"), "{}", stderr(&output));
+}
+
+#[test]
+fn closures_capture_variables_by_reference() {
+    let source = "x = 1\nf = func () { return x }\nx = 2\nprint f()\n\
+        func adders(n)\n{\n    return func (x) { return x + n }, func (d) { n = n + d }\n}\n\
+        add, bump = adders(10)\nprint add(1)\nbump(5)\nprint add(1)";
+    assert_eq!(run(source), "2\n11\n16\n");
+}
+
+#[test]
+fn closures_outlive_their_defining_function() {
+    let source = "func counter()\n{\n    n = 0\n    func next()\n    {\n        n = n + 1\n        return n\n    }\n    return next\n}\n\
+        a = counter()\nb = counter()\nprint a()\nprint a()\nprint b()\nprint a()";
+    assert_eq!(run(source), "1\n2\n1\n3\n");
+}
+
+const PARAMETERS: &str = "func f(a, b = 2, ...rest)\n{\n    return a, b, rest\n}\n";
+
+#[test]
+fn default_named_and_variadic_parameters() {
+    let source = format!("{}print f(1)\nprint f(1, 3, 4, 5)\nprint f(b: 7, a: 1)\nfunc z()\n{{\n    return 0\n}}\nprint z()", PARAMETERS);
+    assert_eq!(run(&source), "(1, 2, [])\n(1, 3, [4, 5])\n(1, 7, [])\n0\n");
+}
+
+#[test]
+fn arity_errors_name_the_parameters() {
+    let error = |call: &str| {
+        let output = freestyle(&["-e", &format!("{}{}", PARAMETERS, call)], "");
+        assert_eq!(output.status.code(), Some(1), "{}", call);
+        stderr(&output).trim_end().to_string()
+    };
+    assert_eq!(error("f()"), "line:5, column:1, runtime error, f() missing argument for parameter 'a'");
+    assert_eq!(error("f(1, c: 2)"), "line:5, column:6, runtime error, f() has no parameter named 'c'");
+    assert_eq!(error("f(1, a: 2)"), "line:5, column:6, runtime error, f() got multiple values for parameter 'a'");
+    assert_eq!(error("func g(a, b)\n{\n    return a\n}\ng()"), "line:9, column:1, runtime error, g() missing arguments for parameters 'a', 'b'");
+    assert_eq!(error("func g(a)\n{\n    return a\n}\ng(1, 2)"), "line:9, column:1, runtime error, g() takes at most 1 positional arguments (a) but 2 were given");
+}
+
+#[test]
+fn invalid_parameter_lists_are_syntax_errors() {
+    assert_eq!(syntax_error("func g(a, a)\n{\n}"), "line:1, column:11, syntax error, duplicate parameter 'a'");
+    assert_eq!(syntax_error("func g(a = 1, b)\n{\n}"), "line:1, column:15, syntax error, parameter 'b' without default follows a parameter with default");
+    assert_eq!(syntax_error("func g(...a, b)\n{\n}"), "line:1, column:12, syntax error, variadic parameter must be the last parameter");
+    assert_eq!(syntax_error("f(a: 1, 2)"), "line:1, column:9, syntax error, positional argument follows named argument 'a'");
+}
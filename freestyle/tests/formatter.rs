@@ -0,0 +1,43 @@
+/// Formats `source` and checks that formatting the result changes nothing.
+fn fmt(source: &str) -> String {
+    let formatted = freestyle::format(source).unwrap();
+    assert_eq!(freestyle::format(&formatted).unwrap(), formatted, "formatting {:?} twice differs", source);
+    formatted
+}
+
+#[test]
+fn spacing() {
+    assert_eq!(fmt("x=1+2*-y\n"), "x = 1 + 2 * -y\n");
+    assert_eq!(fmt("print f(a,b)[0].c\n"), "print f(a, b)[0].c\n");
+    assert_eq!(fmt("m={\"a\":1,\"b\":[1,2]}\n"), "m = {\"a\": 1, \"b\": [1, 2]}\n");
+    assert_eq!(fmt("for i in 0..n{print i}\n"), "for i in 0..n\n{\n    print i\n}\n");
+    assert_eq!(fmt("print - -a\n"), "print - -a\n");
+}
+
+#[test]
+fn nested_negation_with_a_declared_operator() {
+    let source = "operator infix -- precedence 60 (a, b) { return a }\nprint - -a--b\n";
+    let formatted = fmt(source);
+    assert_eq!(formatted, "operator infix -- precedence 60 (a, b)\n{\n    return a\n}\nprint - -a -- b\n");
+    assert!(freestyle::parse(&formatted).unwrap() == freestyle::parse(source).unwrap());
+}
+
+#[test]
+fn blocks_and_blank_lines() {
+    let source = "func f(a) {\nwhile a {\n\n\n\na = a - 1\n}\nreturn a\n}\n\n\n\nprint f(3)\n";
+    let expected = "func f(a)\n{\n    while a\n    {\n        a = a - 1\n    }\n    return a\n}\n\nprint f(3)\n";
+    assert_eq!(fmt(source), expected);
+}
+
+#[test]
+fn comments_are_kept() {
+    let source = "# header\nx=1 # one\n\n\n# before f\nfunc f() {\n# inside\nreturn x # result\n}\n";
+    let expected = "# header\nx = 1 # one\n\n# before f\nfunc f()\n{\n    # inside\n    return x # result\n}\n";
+    assert_eq!(fmt(source), expected);
+}
+
+#[test]
+fn formatted_code_is_unchanged() {
+    let source = "outer: for i in 0..3\n{\n    for j in [1, 2]\n    {\n        continue outer\n    }\n}\nf = func (x, ...rest)\n{\n    return x, rest\n}\n";
+    assert_eq!(fmt(source), source);
+}
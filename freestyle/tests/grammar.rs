@@ -0,0 +1,89 @@
+use freestyle::grammar::{Grammar, Table};
+use std::collections::BTreeSet;
+
+const EXPRESSIONS: &str = "\
+# sums of numbers and parenthesized sums
+NUMBER := \"[0-9]+\"
+EXPR := TERM {\"+\" TERM}
+TERM := NUMBER
+     += \"(\" EXPR \")\"
+";
+
+fn analyze(text: &str) -> (Grammar, Table) {
+    let grammar = Grammar::parse(text).unwrap();
+    let table = grammar.analyze();
+    (grammar, table)
+}
+
+fn rule(grammar: &Grammar, name: &str) -> usize {
+    grammar.nonterminals.iter().position(|nonterminal| nonterminal.name == name).unwrap()
+}
+
+fn token_names(grammar: &Grammar, tokens: &BTreeSet<usize>) -> Vec<String> {
+    tokens.iter().map(|token| grammar.tokens[*token].name.clone()).collect()
+}
+
+#[test]
+fn first_and_follow_sets() {
+    let (grammar, table) = analyze(EXPRESSIONS);
+    let (expr, term) = (rule(&grammar, "EXPR"), rule(&grammar, "TERM"));
+    assert_eq!(token_names(&grammar, &table.first[expr]), ["NUMBER", "L_PAREN"]);
+    assert_eq!(token_names(&grammar, &table.first[term]), ["NUMBER", "L_PAREN"]);
+    assert_eq!(token_names(&grammar, &table.follow[expr]), ["R_PAREN", "EOF"]);
+    assert_eq!(token_names(&grammar, &table.follow[term]), ["PLUS", "R_PAREN", "EOF"]);
+    assert!(table.conflicts.is_empty());
+}
+
+#[test]
+fn nullable_prefixes_add_what_follows() {
+    let (grammar, table) = analyze("LIST := [ITEM] \"end\"\nITEM := \"a\" += \"b\"\n");
+    let (list, item) = (rule(&grammar, "LIST"), rule(&grammar, "ITEM"));
+    assert_eq!(token_names(&grammar, &table.first[list]), ["END", "A", "B"]);
+    assert_eq!(token_names(&grammar, &table.follow[item]), ["END"]);
+    assert!(table.conflicts.is_empty());
+}
+
+#[test]
+fn conflicts_are_reported() {
+    let (_, table) = analyze("S := \"a\" \"b\"\n  += \"a\" \"c\"\n");
+    assert_eq!(table.conflicts, ["LL(1) conflict in S on \"a\":\n    S := \"a\" \"b\"\n    S := \"a\" \"c\""]);
+    let (_, table) = analyze("S := [\"a\"] \"a\"\n");
+    assert_eq!(table.conflicts, ["LL(1) conflict in S.opt1 on \"a\":\n    S.opt1 := \"a\"\n    S.opt1 := (empty)"]);
+    let (_, table) = analyze("NUMBER := \"[0-9]+\"\nE := E \"+\" NUMBER\n  += NUMBER\n");
+    assert_eq!(table.conflicts[0], "rule E is left recursive");
+}
+
+#[test]
+fn grammar_errors_name_the_line() {
+    let err = |text: &str| Grammar::parse(text).err().unwrap();
+    assert_eq!(err("S := T\n"), "line:1, grammar error, undefined symbol T in rule S");
+    assert_eq!(err("S := \"a\"\nS := \"b\"\n"), "line:2, grammar error, S is already defined");
+    assert_eq!(err("S := (\"a\"\n"), "line:1, grammar error, expect ')'");
+    assert_eq!(err("+= \"a\"\n"), "line:1, grammar error, '+=' before the first rule");
+}
+
+#[test]
+fn generated_parser() {
+    let (grammar, table) = analyze(EXPRESSIONS);
+    let code = grammar.generate(&table);
+    assert!(code.starts_with("// Generated by `freestyle compile`, do not edit.\n"));
+    assert!(code.contains("pub enum TokenType {\n    Number,\n    Plus,\n    LParen,\n    RParen,\n    Eof,\n}\n"), "{}", code);
+    assert!(code.contains("pub enum NodeType {\n    Expr,\n    Term,\n}\n"), "{}", code);
+    assert!(code.contains("        TokenType::Plus => Some(\"+\"),\n"), "{}", code);
+    assert!(code.contains("        TokenType::Number => Some(\"[0-9]+\"),\n"), "{}", code);
+    assert!(code.contains("    // TERM := \"(\" EXPR \")\"\n    &[Symbol::Token(TokenType::LParen), Symbol::Rule(0), Symbol::Token(TokenType::RParen)],\n"), "{}", code);
+    // One entry per (rule, lookahead) of the table.
+    assert_eq!(code.matches(") => Some(").count(), table.predict.len());
+    assert!(code.contains("pub const TOKENS: [TokenType; 5] = [\n    TokenType::Number,\n"), "{}", code);
+    assert!(code.contains("        TokenType::LParen => \"L_PAREN\",\n"), "{}", code);
+    assert!(code.contains("pub fn parse(tokens: &[TokenType]) -> Result<Node, SyntaxError> {\n    let mut root = Node { node_type: RULES[0].unwrap()"));
+}
+
+#[test]
+fn language_grammar_is_ll1() {
+    let (grammar, table) = analyze(include_str!("../grammar.txt"));
+    assert_eq!(table.conflicts, Vec::<String>::new());
+    let statement = rule(&grammar, "STATEMENT");
+    assert!(token_names(&grammar, &table.first[statement]).contains(&"OPERATOR".to_string()));
+    assert!(grammar.generate(&table).contains("    OperatorDeclaration,\n"));
+}
@@ -0,0 +1,82 @@
+use freestyle::printer;
+
+/// Prints `source` and checks that the result parses back to the same AST.
+fn print(source: &str) -> String {
+    let parse = |source: &str| freestyle::parse(source).unwrap_or_else(|err| panic!("{}\n{}", source, err));
+    let ast = parse(source);
+    let printed = printer::program(&ast);
+    assert!(parse(&printed) == ast, "{:?} printed as {:?} parses differently", source, printed);
+    printed
+}
+
+/// `print <expr>` reprinted, without the `print`.
+fn expr(source: &str) -> String {
+    let printed = print(&format!("print {}", source));
+    printed.trim_end().trim_start_matches("print ").to_string()
+}
+
+#[test]
+fn associativity() {
+    assert_eq!(expr("a - (b - c)"), "a - (b - c)");
+    assert_eq!(expr("(a - b) - c"), "a - b - c");
+    assert_eq!(expr("(a ^ b) ^ c"), "(a ^ b) ^ c");
+    assert_eq!(expr("a ^ (b ^ c)"), "a ^ b ^ c");
+    assert_eq!(expr("(a < b) < c"), "(a < b) < c");
+    assert_eq!(expr("a * (b + c) % d"), "a * (b + c) % d");
+    assert_eq!(expr("(1..2)"), "1..2");
+}
+
+#[test]
+fn prefix_operators() {
+    assert_eq!(expr("-a ^ b"), "-a ^ b");
+    assert_eq!(expr("(-a) ^ b"), "(-a) ^ b");
+    assert_eq!(expr("-(a ^ b)"), "-a ^ b");
+    assert_eq!(expr("-(a * b)"), "-(a * b)");
+    assert_eq!(expr("(-a) * b"), "-a * b");
+    assert_eq!(expr("- -a"), "- -a");
+    assert_eq!(expr("-(-a)"), "- -a");
+    assert_eq!(expr("-(-(-a ^ b))"), "- - -a ^ b");
+    assert_eq!(expr("-((-a) ^ b)"), "-(-a) ^ b");
+    let source = "operator infix -- precedence 60 (a, b)\n{\n    return a\n}\nprint - -a -- b\n";
+    assert_eq!(print(source), source);
+    assert_eq!(expr("not a == b"), "not a == b");
+    assert_eq!(expr("(not a) == b"), "(not a) == b");
+    assert_eq!(expr("not (a and b)"), "not (a and b)");
+    assert_eq!(expr("(not a) and b"), "not a and b");
+    assert_eq!(expr("not not a"), "not not a");
+}
+
+#[test]
+fn tuples() {
+    assert_eq!(print("x = 1, 2\n"), "x = 1, 2\n");
+    assert_eq!(print("a, (b, c) = (1,), ((2, 3), 4)\n"), "a, (b, c) = (1,), ((2, 3), 4)\n");
+    assert_eq!(expr("f((1, 2), [(3, 4)])"), "f((1, 2), [(3, 4)])");
+    assert_eq!(print("for i, j in pairs\n{\n    print (i, j)\n}\n"), "for i, j in pairs\n{\n    print (i, j)\n}\n");
+    assert_eq!(print("func f()\n{\n    return 1, 2\n}\n"), "func f()\n{\n    return 1, 2\n}\n");
+}
+
+#[test]
+fn lambdas() {
+    let source = "f = func(a, b = 1, ...rest) { return a }\n";
+    assert_eq!(print(source), "f = func (a, b = 1, ...rest)\n{\n    return a\n}\n");
+    assert_eq!(print("map(xs, func(x) { return x * 2 })\n"), "map(xs, func (x)\n{\n    return x * 2\n})\n");
+    assert_eq!(expr("(func() { return 1 })()"), "func ()\n{\n    return 1\n}()");
+}
+
+#[test]
+fn postfix_operations_on_parenthesized_expressions() {
+    assert_eq!(expr("(a + b)(c)"), "(a + b)(c)");
+    assert_eq!(expr("(a - b).c"), "(a - b).c");
+    assert_eq!(expr("(-a)[0]"), "(-a)[0]");
+    assert_eq!(expr("-a[0]"), "-a[0]");
+    assert_eq!(expr("(1, 2)[0]"), "(1, 2)[0]");
+    assert_eq!(expr("(f)(x).y[z]"), "f(x).y[z]");
+    assert_eq!(expr("(not a).b"), "(not a).b");
+}
+
+#[test]
+fn custom_operators() {
+    let source = "operator infix <+> precedence 30 (a, b)\n{\n    return a\n}\nx = (a <+> b) * c, a <+> (b <+> c), a + b <+> c\n";
+    let printed = print(source);
+    assert!(printed.ends_with("x = (a <+> b) * c, a <+> (b <+> c), a + b <+> c\n"), "{}", printed);
+}
@@ -0,0 +1,92 @@
+use freestyle::error::ErrorKind;
+use freestyle::TokenType;
+
+fn tokens(source: &str) -> Vec<(TokenType, String)> {
+    freestyle::tokenize(source).unwrap().into_iter().map(|token| (token.token_type, token.literal)).collect()
+}
+
+fn declare(op: &str) -> String {
+    format!("operator infix {} precedence 60 (a, b)\n{{\n    return a\n}}\n", op)
+}
+
+/// Tokens of the last line of `source`, without the `Eof`.
+fn last_line(source: &str) -> Vec<(TokenType, String)> {
+    let mut tokens = tokens(source);
+    tokens.pop();
+    let start = tokens.iter().rposition(|(token_type, _)| *token_type == TokenType::Newline).map_or(0, |i| i + 1);
+    tokens.split_off(start)
+}
+
+fn token(token_type: TokenType, literal: &str) -> (TokenType, String) {
+    (token_type, literal.to_string())
+}
+
+#[test]
+fn operators_may_not_shadow_built_in_tokens() {
+    for (op, builtin) in [("=", "="), ("!", "!="), ("<", "<"), (">", ">"), ("+", "+"), ("^", "^")] {
+        let err = freestyle::parse(&declare(op)).err().unwrap_or_else(|| panic!("operator {} was accepted", op));
+        assert_eq!(err.kind, ErrorKind::Syntax);
+        let expected = format!("line:1, column:16, syntax error, operator '{}' clashes with the built-in '{}'", op, builtin);
+        assert_eq!(err.message, expected);
+    }
+}
+
+#[test]
+fn operators_take_part_in_the_longest_match() {
+    use TokenType::*;
+    let declarations = declare("=~") + &declare("<=>");
+    let line = |code: &str| last_line(&format!("{}{}", declarations, code));
+    assert_eq!(line("x = 3"), [token(Symbol, "x"), token(Assign, "="), token(Integer, "3")]);
+    assert_eq!(line("x == 3"), [token(Symbol, "x"), token(Eq, "=="), token(Integer, "3")]);
+    assert_eq!(line("x =~ y"), [token(Symbol, "x"), token(CustomOp, "=~"), token(Symbol, "y")]);
+    assert_eq!(line("x <= y"), [token(Symbol, "x"), token(Le, "<="), token(Symbol, "y")]);
+    assert_eq!(line("x<=>y"), [token(Symbol, "x"), token(CustomOp, "<=>"), token(Symbol, "y")]);
+}
+
+fn positions(source: &str) -> Vec<(u32, u32)> {
+    freestyle::tokenize(source).unwrap().iter().map(|token| (token.row, token.col)).collect()
+}
+
+fn error(source: &str) -> String {
+    freestyle::tokenize(source).err().unwrap().message
+}
+
+#[test]
+fn names_and_integers_keep_the_following_delimiter() {
+    use TokenType::*;
+    // Names and integers used to swallow the character after them.
+    assert_eq!(tokens("f(x)\nprint 12)"), [
+        token(Symbol, "f"), token(LP, "("), token(Symbol, "x"), token(RP, ")"), token(Newline, "\n"),
+        token(Print, "print"), token(Integer, "12"), token(RP, ")"), token(Eof, ""),
+    ]);
+    assert_eq!(tokens("a\nb"), [token(Symbol, "a"), token(Newline, "\n"), token(Symbol, "b"), token(Eof, "")]);
+    assert_eq!(tokens("[1,2]"), [
+        token(LSquare, "["), token(Integer, "1"), token(Comma, ","), token(Integer, "2"), token(RSquare, "]"), token(Eof, ""),
+    ]);
+}
+
+#[test]
+fn tokens_know_their_position() {
+    assert_eq!(positions("x = 10\n  y..=z"), [(1, 1), (1, 3), (1, 5), (1, 7), (2, 3), (2, 4), (2, 7), (2, 8)]);
+}
+
+#[test]
+fn keywords_comments_and_strings() {
+    use TokenType::*;
+    assert_eq!(tokens("while whilex # while\n"), [
+        token(While, "while"), token(Symbol, "whilex"), token(Newline, "\n"), token(Eof, ""),
+    ]);
+    assert_eq!(tokens("\"a\\\"b\\n\" 变量"), [token(Str, "a\"b\n"), token(Symbol, "变量"), token(Eof, "")]);
+    assert_eq!(tokens("a...b..c.d"), [
+        token(Symbol, "a"), token(Ellipsis, "..."), token(Symbol, "b"), token(DotDot, ".."), token(Symbol, "c"),
+        token(Dot, "."), token(Symbol, "d"), token(Eof, ""),
+    ]);
+}
+
+#[test]
+fn lexical_errors() {
+    assert_eq!(error("x = \"abc"), "line:1, column:5, unterminated string literal");
+    assert_eq!(error("x = 12ab"), "line:1, column:7, unexpected character a");
+    assert_eq!(error("\n  ;"), "line:2, column:3, unexpected character ;");
+    assert_eq!(error("\"a\\q\""), "line:1, column:3, unknown escape sequence \\q");
+}
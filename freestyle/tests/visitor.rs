@@ -0,0 +1,146 @@
+use freestyle::ast::{self, Arg, Expr, FuncDef, Param, Stmt, Target, Visitor, VisitorMut};
+use std::collections::BTreeSet;
+
+/// Uses every kind of node.
+const PROGRAM: &str = "\
+operator infix <+> precedence 60 (a, b)
+{
+    return a + b
+}
+func f(a, b = 2, ...rest)
+{
+    outer: while a < 10
+    {
+        for i, j in 0..=3
+        {
+            break outer
+        }
+        continue
+    }
+    return
+}
+x = [1, \"s\", (2, 3), {\"k\": -4}]
+x[0], y.z = not a, b.c
+print f(1, b: 2)(3)[x] <+> func(q) { return q }
+f()
+";
+
+fn parse(source: &str) -> Vec<Stmt> {
+    freestyle::parse(source).unwrap()
+}
+
+/// Names every node kind it is shown, listing them here keeps the test in
+/// step with the AST: a new kind doesn't compile until it is named.
+#[derive(Default)]
+struct Kinds(BTreeSet<&'static str>);
+
+impl Visitor for Kinds {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.0.insert(match stmt {
+            Stmt::Print { .. } => "print",
+            Stmt::Assign { .. } => "assign",
+            Stmt::While { .. } => "while",
+            Stmt::For { .. } => "for",
+            Stmt::Break { .. } => "break",
+            Stmt::Continue { .. } => "continue",
+            Stmt::Return { .. } => "return",
+            Stmt::FuncDecl { .. } => "func",
+            Stmt::OperatorDecl { .. } => "operator",
+            Stmt::Expression { .. } => "expression",
+        });
+        ast::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.0.insert(match expr {
+            Expr::Integer { .. } => "integer",
+            Expr::Str { .. } => "string",
+            Expr::Name { .. } => "name",
+            Expr::List { .. } => "list",
+            Expr::Tuple { .. } => "tuple",
+            Expr::Map { .. } => "map",
+            Expr::Index { .. } => "index",
+            Expr::Member { .. } => "member",
+            Expr::Call { .. } => "call",
+            Expr::Lambda { .. } => "lambda",
+            Expr::Unary { .. } => "unary",
+            Expr::Binary { .. } => "binary",
+        });
+        ast::walk_expr(self, expr);
+    }
+
+    fn visit_target(&mut self, target: &Target) {
+        self.0.insert(match target {
+            Target::Name { .. } => "name target",
+            Target::Index { .. } => "index target",
+            Target::Member { .. } => "member target",
+            Target::Tuple { .. } => "tuple target",
+        });
+        ast::walk_target(self, target);
+    }
+
+    fn visit_arg(&mut self, arg: &Arg) {
+        self.0.insert(match arg {
+            Arg::Positional(_) => "positional",
+            Arg::Named { .. } => "named",
+        });
+        ast::walk_arg(self, arg);
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        self.0.insert(match param {
+            Param::Required { .. } => "required",
+            Param::Optional { .. } => "optional",
+            Param::Variadic { .. } => "variadic",
+        });
+        ast::walk_param(self, param);
+    }
+
+    fn visit_func_def(&mut self, function: &FuncDef) {
+        self.0.insert("function");
+        ast::walk_func_def(self, function);
+    }
+}
+
+#[test]
+fn visitor_walks_every_node_kind() {
+    let mut kinds = Kinds::default();
+    ast::walk_program(&mut kinds, &parse(PROGRAM));
+    let expected = [
+        "print", "assign", "while", "for", "break", "continue", "return", "func", "operator", "expression",
+        "integer", "string", "name", "list", "tuple", "map", "index", "member", "call", "lambda", "unary", "binary",
+        "name target", "index target", "member target", "tuple target",
+        "positional", "named", "required", "optional", "variadic", "function",
+    ];
+    assert_eq!(kinds.0, expected.iter().copied().collect());
+}
+
+/// Renames every use of a variable, parameters are left alone.
+struct Rename(&'static str, &'static str);
+
+impl VisitorMut for Rename {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Name { token } if token.literal == self.0 => token.literal = self.1.to_string(),
+            _ => ast::walk_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_target_mut(&mut self, target: &mut Target) {
+        match target {
+            Target::Name { token } if token.literal == self.0 => token.literal = self.1.to_string(),
+            _ => ast::walk_target_mut(self, target),
+        }
+    }
+}
+
+#[test]
+fn visitor_mut_rewrites_in_place() {
+    let source = "func f(a)\n{\n    a = a + 1\n    return [a, b]\n}\nprint f(a)\n";
+    let mut program = parse(source);
+    let shared = program.clone();
+    ast::walk_program_mut(&mut Rename("a", "c"), &mut program);
+    assert_eq!(freestyle::printer::program(&program), "func f(a)\n{\n    c = c + 1\n    return [c, b]\n}\nprint f(c)\n");
+    // Function bodies shared with another tree are copied, not changed.
+    assert_eq!(freestyle::printer::program(&shared), source);
+}